@@ -0,0 +1,320 @@
+use std::{cmp::Reverse, collections::BTreeMap, fmt, mem};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+use serde::Serialize;
+
+use crate::discord::Message;
+use crate::zone::Zone;
+
+#[rustfmt::skip]
+pub const HOUR_LABELS: [&str; 24] = [
+    "12am", "1am", "2am", "3am", "4am", "5am", "6am", "7am",
+    "8am", "9am", "10am", "11am", "12pm", "1pm", "2pm", "3pm",
+    "4pm", "5pm", "6pm", "7pm", "8pm", "9pm", "10pm", "11pm",
+];
+
+/// messages we want before guessing anything
+const MIN_MESSAGES: u32 = 50;
+/// shortest stretch we'd believe is someone sleeping
+const MIN_SLEEP: usize = 4;
+/// an hour counts as quiet below this fraction of the average hour
+const QUIET: f32 = 0.25;
+/// a trough shallower than this, against the average hour, isn't sleep
+const MIN_CONTRAST: f32 = 0.05;
+/// local hour the middle of a night's sleep tends to land on
+const SLEEP_MIDPOINT: f32 = 4.0;
+
+/// where the target most likely lives, going by when they go quiet
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct ZoneGuess {
+    // hours east of UTC
+    pub offset: i32,
+    // 0 to 1
+    pub confidence: f32,
+    // UTC hour the quiet stretch starts at and how many hours it lasts
+    pub sleep_start: usize,
+    pub sleep_len: usize,
+}
+
+impl fmt::Display for ZoneGuess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "UTC{:+} ({:.0}% confident)", self.offset, self.confidence * 100.0)
+    }
+}
+
+/// messages per hour of the day, in UTC
+pub fn utc_hours(messages: &[Message]) -> [u32; 24] {
+    messages.iter().fold([0; 24], |mut acc, m| {
+        if let Some(t) = m.time() {
+            acc[t.hour() as usize] += 1;
+        }
+        acc
+    })
+}
+
+/// messages per hour, per weekday and hour, and per calendar day in a zone,
+/// kept up to date as messages come in so reading them costs nothing.
+/// each timestamp is parsed once, and kept around to re-bucket from when
+/// the zone changes
+#[derive(Debug, Default)]
+pub struct Tally {
+    zone: Zone,
+    times: Vec<DateTime<Utc>>,
+    utc_hours: [u32; 24],
+    hours: [u32; 24],
+    weekday_hours: [[u32; 24]; 7],
+    daily: BTreeMap<NaiveDate, u32>,
+}
+
+impl Tally {
+    pub fn new(zone: Zone) -> Self {
+        Tally {
+            zone,
+            ..Tally::default()
+        }
+    }
+
+    /// tally up `messages` in one go
+    pub fn of(messages: &[Message], zone: Zone) -> Self {
+        let mut tally = Tally::new(zone);
+        messages.iter().for_each(|m| tally.add(m));
+        tally
+    }
+
+    pub fn add(&mut self, message: &Message) {
+        if let Some(t) = message.time() {
+            self.times.push(t);
+            self.count(t);
+        }
+    }
+
+    fn count(&mut self, t: DateTime<Utc>) {
+        let local = self.zone.localize(t);
+        self.utc_hours[t.hour() as usize] += 1;
+        self.hours[local.hour() as usize] += 1;
+        self.weekday_hours[local.weekday().num_days_from_monday() as usize][local.hour() as usize] += 1;
+        *self.daily.entry(local.date()).or_insert(0) += 1;
+    }
+
+    pub fn clear(&mut self) {
+        *self = Tally::new(self.zone);
+    }
+
+    /// count everything again in another zone
+    pub fn set_zone(&mut self, zone: Zone) {
+        let times = mem::take(&mut self.times);
+        *self = Tally::new(zone);
+        for t in times {
+            self.times.push(t);
+            self.count(t);
+        }
+    }
+
+    pub fn zone(&self) -> Zone {
+        self.zone
+    }
+
+    pub fn utc_hours(&self) -> &[u32; 24] {
+        &self.utc_hours
+    }
+
+    pub fn hours(&self) -> &[u32; 24] {
+        &self.hours
+    }
+
+    pub fn weekday_hours(&self) -> &[[u32; 24]; 7] {
+        &self.weekday_hours
+    }
+
+    pub fn daily(&self) -> &BTreeMap<NaiveDate, u32> {
+        &self.daily
+    }
+
+    /// the headline numbers, with streaks and gaps counted up to `until`
+    pub fn stats(&self, until: NaiveDate) -> Stats {
+        let peak = |hours: &[u32; 24]| (0..24).filter(|&h| hours[h] > 0).max_by_key(|&h| hours[h]);
+        Stats {
+            messages: self.times.len(),
+            peak_hour: peak(&self.hours),
+            active_days: self.daily.len(),
+            busiest_day: self.daily.iter().max_by_key(|&(_, &n)| n).map(|(&day, &n)| (day, n)),
+            streaks: streaks(&self.daily, until),
+            zone_guess: guess_zone(&self.utc_hours),
+        }
+    }
+}
+
+/// what a tally adds up to
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub messages: usize,
+    // busiest hour of the day in the tally's zone
+    pub peak_hour: Option<usize>,
+    // days with at least one message
+    pub active_days: usize,
+    pub busiest_day: Option<(NaiveDate, u32)>,
+    pub streaks: Streaks,
+    pub zone_guess: Option<ZoneGuess>,
+}
+
+/// stretch of consecutive days
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Run {
+    pub start: NaiveDate,
+    pub days: i64,
+}
+
+impl Run {
+    pub fn end(&self) -> NaiveDate {
+        self.start + Duration::days(self.days - 1)
+    }
+}
+
+impl fmt::Display for Run {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} day{} ({} – {})", self.days, if self.days == 1 { "" } else { "s" },
+            self.start.format("%Y-%m-%d"), self.end().format("%Y-%m-%d"))
+    }
+}
+
+/// longest runs of active and silent days
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Streaks {
+    pub active: Option<Run>,
+    pub gap: Option<Run>,
+}
+
+/// walk every day from the first active one up to `until`, so a
+/// silence that's still going counts as a gap too
+pub fn streaks(days: &BTreeMap<NaiveDate, u32>, until: NaiveDate) -> Streaks {
+    let mut streaks = Streaks::default();
+    let first = match days.keys().next() {
+        Some(&first) => first,
+        None => return streaks,
+    };
+
+    let longest = |best: &mut Option<Run>, run: Run| {
+        if best.is_none_or(|b| run.days > b.days) {
+            *best = Some(run);
+        }
+    };
+    let mut current: Option<(bool, Run)> = None;
+    for day in first.iter_days().take_while(|d| *d <= until.max(first)) {
+        let active = days.get(&day).copied().unwrap_or(0) > 0;
+        current = match current {
+            Some((was, run)) if was == active => Some((was, Run { days: run.days + 1, ..run })),
+            _ => Some((active, Run { start: day, days: 1 })),
+        };
+        if let Some((active, run)) = current {
+            longest(if active { &mut streaks.active } else { &mut streaks.gap }, run);
+        }
+    }
+    streaks
+}
+
+/// treat the longest quiet stretch of the day as sleep and work out
+/// which UTC offset puts it in the middle of the night
+pub fn guess_zone(hours: &[u32; 24]) -> Option<ZoneGuess> {
+    let total = hours.iter().sum::<u32>();
+    if total < MIN_MESSAGES {
+        return None;
+    }
+    let mean = total as f32 / 24.0;
+    let at = |h: usize| hours[h % 24];
+    let quiet = |h: usize| (at(h) as f32) < mean * QUIET;
+
+    // the longest run of quiet hours, wrapping past midnight. some hour is
+    // always at or above the mean, so a run can't go all the way round
+    let run = |start: usize| (0..24).take_while(|&i| quiet(start + i)).count();
+    let (start, len) = (0..24)
+        .filter(|&h| quiet(h) && !quiet(h + 23))
+        .map(|h| (h, run(h)))
+        // the quieter of two as long
+        .max_by_key(|&(h, len)| (len, Reverse((h..h + len).map(at).sum::<u32>())))?;
+    // someone who never goes quiet for long gives nothing away
+    if len < MIN_SLEEP {
+        return None;
+    }
+
+    let midpoint = start as f32 + len as f32 / 2.0;
+    let offset = ((SLEEP_MIDPOINT - midpoint).round() as i32 + 12).rem_euclid(24) - 12;
+
+    // a deep trough and plenty of messages make for a confident guess
+    let asleep = (start..start + len).map(at).sum::<u32>() as f32 / len as f32;
+    let contrast = (1.0 - asleep / mean).clamp(0.0, 1.0);
+    if contrast < MIN_CONTRAST {
+        return None;
+    }
+    let sample = 1.0 - (-(total as f32) / 500.0).exp();
+    // nobody sleeps 20 hours a day
+    let plausible = if len <= 12 { 1.0 } else { 12.0 / len as f32 };
+
+    Some(ZoneGuess {
+        offset,
+        confidence: contrast * sample * plausible,
+        sleep_start: start,
+        sleep_len: len,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 50 messages an hour, none in `asleep` (utc hours)
+    fn hours(asleep: &[usize]) -> [u32; 24] {
+        let mut hours = [50; 24];
+        asleep.iter().for_each(|&h| hours[h] = 0);
+        hours
+    }
+
+    #[test]
+    fn guesses_the_offset_from_the_quiet_hours() {
+        // asleep 2am to 10am UTC, so 4am local lands on 6am UTC
+        let guess = guess_zone(&hours(&[2, 3, 4, 5, 6, 7, 8, 9])).unwrap();
+        assert_eq!(guess.offset, -2);
+        assert_eq!((guess.sleep_start, guess.sleep_len), (2, 8));
+        assert!(guess.confidence > 0.5);
+    }
+
+    #[test]
+    fn sleep_can_wrap_past_midnight() {
+        // asleep 10pm to 4am UTC, the middle of it at 1am UTC
+        let guess = guess_zone(&hours(&[22, 23, 0, 1, 2, 3])).unwrap();
+        assert_eq!(guess.offset, 3);
+        assert_eq!((guess.sleep_start, guess.sleep_len), (22, 6));
+    }
+
+    #[test]
+    fn no_guess_without_a_quiet_stretch() {
+        assert_eq!(guess_zone(&[50; 24]), None);
+        // a dip, but nowhere near quiet
+        let mut hours = [50; 24];
+        hours[3] = 40;
+        assert_eq!(guess_zone(&hours), None);
+    }
+
+    #[test]
+    fn goes_by_the_longest_quiet_stretch() {
+        // a long night of the odd message beats a short dead spell in the afternoon
+        let mut hours = [20; 24];
+        hours[0..8].fill(1);
+        hours[14..18].fill(0);
+        let guess = guess_zone(&hours).unwrap();
+        assert_eq!((guess.sleep_start, guess.sleep_len), (0, 8));
+        assert_eq!(guess.offset, 0);
+    }
+
+    #[test]
+    fn no_guess_from_short_gaps() {
+        // a message every three hours around the clock
+        let hours: [u32; 24] = std::array::from_fn(|h| if h % 3 == 0 { 20 } else { 0 });
+        assert_eq!(guess_zone(&hours), None);
+    }
+
+    #[test]
+    fn no_guess_from_too_few_messages() {
+        let mut hours = [0; 24];
+        hours[12] = MIN_MESSAGES - 1;
+        assert_eq!(guess_zone(&hours), None);
+    }
+}
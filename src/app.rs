@@ -1,7 +1,7 @@
-use std::{io, thread, process, time::Duration};
+use std::{io, path::{Path, PathBuf}, time::{Duration, Instant}};
 use tui::{
     backend::{CrosstermBackend, Backend},
-    widgets::{Block, Borders, Paragraph, List, ListItem, BarChart},
+    widgets::{Block, Borders, Paragraph, List, ListItem, ListState, BarChart},
     layout::{Layout, Constraint, Direction, Rect, Alignment},
     text::{Span, Spans},
     style::{Style, Color, Modifier},
@@ -14,15 +14,27 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use unicode_width::UnicodeWidthStr;
-use chrono::{DateTime, Timelike};
+use chrono::{self, DateTime, Datelike, Utc};
 
-use crate::discord::{self, User, Channel, Guild, Message, Messenger};
+use how_active::analysis::{self, Tally, HOUR_LABELS};
+use how_active::cache::{Cache, Checkpoint, Fetch, Key};
+use how_active::export;
+use how_active::discord::{self, User, Channel, Client, Guild, Message, Wait};
+use how_active::range::DateRange;
+use how_active::worker::{Update, Worker};
+use how_active::zone::Zone;
+
+use crate::config::{self, Theme};
 
 #[derive(PartialEq, Eq)]
 enum Mode {
     Normal,
     User,
     Channel,
+    Range,
+    Zone,
+    // picking one of the saved targets
+    Targets,
 }
 
 // A Channel or Guild
@@ -31,15 +43,39 @@ enum Location {
     Guild,
 }
 
+// What the middle of the screen shows
+#[derive(Clone, Copy)]
+enum View {
+    Bars,
+    Heatmap,
+    Calendar,
+}
+
 // Current action state
 enum State {
     Idle,
     Working,
+    Waiting(Wait),
     Done,
+    // last request went wrong
+    Failed(discord::Error),
+    // couldn't make sense of what was typed in
+    BadInput(String),
+    // wrote something out to this file
+    Saved(PathBuf),
+    // run was stopped halfway
+    Cancelled,
+}
+
+// A fetch in progress
+struct Run {
+    worker: Worker,
+    // Keeps the checkpoint and the cache up to date
+    fetch: Fetch,
 }
 
 pub struct App {
-    pub token: String,
+    pub client: Client,
     // User whomst the token belongs to
     user: User,
     // Current Discord IDs of target user and channel
@@ -48,107 +84,368 @@ pub struct App {
     target_guil: Guild,
     // Switch between Channel and Guild
     target_loc: Location,
+    // Only look at messages sent within this window
+    pub range: DateRange,
+    // Chart in the middle
+    view: View,
+    // Where to write an export once a run is done
+    pub export: Option<PathBuf>,
+    // Where exports from the keys go
+    pub export_dir: Option<PathBuf>,
+    // Colours to draw the charts in
+    pub theme: Theme,
+    // Targets saved in the config and which one is highlighted
+    pub targets: Vec<config::Target>,
+    picked: ListState,
+    // Where fetched messages are kept between runs
+    pub cache: Option<Cache>,
+    // Carry on with the last unfinished fetch as soon as the ui is up
+    pub resume: bool,
+    // Fetch going on in the background
+    run: Option<Run>,
+    // When the last run finished
+    fetched_at: Option<DateTime<Utc>>,
     // Current input mode of tui
     input_mode: Mode,
     // Active user input
     input_user: String,
     input_chan: String,
+    input_range: String,
+    input_zone: String,
     // Messages that have been read
     messages: Vec<Message>,
+    // What the charts show, counted as the messages come in and bucketed in the chosen zone
+    tally: Tally,
     // Done indicator
     state: State,
     progress: (usize, u32)
 }
 
 impl App {
-    pub fn new(token: String) -> reqwest::Result<Self> {
+    pub fn new(client: Client) -> discord::Result<Self> {
         Ok(App {
-            user: discord::get_user("@me", &token)?,
-            token: token,
+            user: discord::get_user("@me", &client)?,
+            client,
             target_user: User::default(),
             target_chan: Channel::default(),
             target_guil: Guild::default(),
             target_loc: Location::Channel,
+            range: DateRange::default(),
+            view: View::Bars,
+            export: None,
+            export_dir: None,
+            theme: Theme::default(),
+            targets: Vec::new(),
+            picked: ListState::default(),
+            cache: None,
+            resume: false,
+            run: None,
+            fetched_at: None,
             input_mode: Mode::Normal,
             input_user: String::new(),
             input_chan: String::new(),
+            input_range: String::new(),
+            input_zone: String::new(),
             messages: Vec::new(),
+            tally: Tally::default(),
             state: State::Idle,
             progress: (0, 0),
         })
     }
 
     // check that the id is valid and set it as the target
-    fn set_target_user(&mut self, id: &str) -> reqwest::Result<()> {
-        self.target_user = discord::get_user(id, &self.token)?;
+    fn set_target_user(&mut self, id: &str) -> discord::Result<()> {
+        self.target_user = discord::get_user(id, &self.client)?;
         Ok(())
     }
 
-    fn set_target_chan(&mut self, id: &str) -> reqwest::Result<()> {
-        self.target_chan = match discord::get_channel(id, &self.token) {
+    fn set_target_chan(&mut self, id: &str) -> discord::Result<()> {
+        self.target_chan = match discord::get_channel(id, &self.client) {
             Ok(l) => {
                 self.target_loc = Location::Channel;
                 l
             },
-            Err(_) => {
-                self.set_target_guil(id);
+            // no such channel, the id might belong to a guild instead
+            Err(discord::Error::NotFound) => {
+                self.set_target_guil(id)?;
                 Channel::default()
             },
+            Err(e) => return Err(e),
         };
         Ok(())
     }
 
-    fn set_target_guil(&mut self, id: &str) {
-        self.target_guil = match discord::get_guild(id, &self.token) {
+    // take whatever's typed into the user box as the target
+    fn submit_user(&mut self) {
+        self.set_messages(Vec::new());
+
+        let user_id = self.input_user.drain(..).collect::<String>();
+        self.state = match self.set_target_user(&user_id) {
+            Ok(()) => State::Idle,
+            Err(e) => State::Failed(e),
+        };
+    }
+
+    fn submit_chan(&mut self) {
+        self.set_messages(Vec::new());
+
+        let chan_id = self.input_chan.drain(..).collect::<String>();
+        self.state = match self.set_target_chan(&chan_id) {
+            Ok(()) => State::Idle,
+            Err(e) => State::Failed(e),
+        };
+    }
+
+    // fill the input boxes from the highlighted saved target and submit them
+    fn submit_picked(&mut self) {
+        let target = match self.picked.selected().and_then(|i| self.targets.get(i)) {
+            Some(t) => t.clone(),
+            None => return,
+        };
+        self.input_user = target.user.clone();
+        self.input_chan = target.location().unwrap_or_default().to_string();
+
+        self.submit_user();
+        if let State::Failed(_) = self.state {
+            return;
+        }
+        if !self.input_chan.is_empty() {
+            self.submit_chan();
+        }
+    }
+
+    // move the highlight through the saved targets, wrapping around
+    fn pick(&mut self, forward: bool) {
+        let len = self.targets.len();
+        if len == 0 {
+            return;
+        }
+        let i = match self.picked.selected() {
+            Some(i) if forward => (i + 1) % len,
+            Some(i) => (i + len - 1) % len,
+            None => 0,
+        };
+        self.picked.select(Some(i));
+    }
+
+    fn set_target_guil(&mut self, id: &str) -> discord::Result<()> {
+        match discord::get_guild(id, &self.client) {
             Ok(l) => {
                 self.target_loc = Location::Guild;
-                l
+                self.target_guil = l;
+                Ok(())
+            },
+            Err(e) => {
+                self.target_guil = Guild::default();
+                Err(e)
+            },
+        }
+    }
+
+    // carry on with the last fetch that didn't finish, aiming at whatever it was after
+    fn resume(&mut self) {
+        let resumed = match self.cache.as_ref().map(Cache::resume) {
+            Some(Ok(Some(resumed))) => resumed,
+            Some(Ok(None)) | None => {
+                self.state = State::BadInput(String::from("No unfinished fetch to resume"));
+                return;
+            },
+            Some(Err(e)) => {
+                self.state = State::BadInput(e.to_string());
+                return;
             },
-            Err(_) => Guild::default(),
         };
+
+        let position = &resumed.0.position;
+        let location = position.channel.as_deref().unwrap_or(&position.guild);
+        if let Err(e) = self.preset(Some(&position.user), Some(location)) {
+            self.state = State::Failed(e);
+            return;
+        }
+        self.range = resumed.0.range;
+        self.start(Some(resumed));
     }
 
-    fn start<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> io::Result<()> {
-        self.state = State::Working;
-        self.messages.clear();
-        terminal.draw(|f| draw(f, self))?;
-
-        let mut messenger = match self.target_loc {
-            Location::Channel => Messenger::new(
-                self.token.clone(),
-                self.target_user.id.clone(),
-                self.target_chan.guild_id.clone(),
-                Some(self.target_chan.id.clone()),
-            ),
-            Location::Guild => Messenger::new(
-                self.token.clone(),
-                self.target_user.id.clone(),
-                self.target_guil.id.clone(),
-                None,
-            ),
+    // kick off a fetch on a worker thread, `update` takes it from there
+    fn start(&mut self, resumed: Option<(Checkpoint, Vec<Message>)>) {
+        let key = match self.target_loc {
+            Location::Channel => Key {
+                guild: self.target_chan.guild_id.clone(),
+                channel: Some(self.target_chan.id.clone()),
+                author: self.target_user.id.clone(),
+            },
+            Location::Guild => Key {
+                guild: self.target_guil.id.clone(),
+                channel: None,
+                author: self.target_user.id.clone(),
+            },
+        };
+        // the worker would only fail on these, say what's missing instead
+        let missing = if key.author.is_empty() {
+            Some("No target user, hit i to enter one")
+        } else if key.guild.is_empty() && key.channel.as_deref().is_some_and(|c| !c.is_empty()) {
+            Some("DMs can't be searched, pick a channel in a guild")
+        } else if key.guild.is_empty() {
+            Some("No channel or guild, hit a to enter one")
+        } else {
+            None
+        };
+        if let Some(missing) = missing {
+            self.state = State::BadInput(String::from(missing));
+            return;
+        }
+        let (fetch, messenger) = match Fetch::start(&self.client, self.cache.clone(), key, self.range, resumed) {
+            Ok(started) => started,
+            Err(e) => {
+                self.state = State::BadInput(e.to_string());
+                return;
+            },
         };
+        // show what's there already straight away, new messages go on top
+        self.set_messages(fetch.so_far());
+
+        self.state = State::Working;
+        self.progress = (self.messages.len(), self.messages.len() as u32);
+        self.run = Some(Run {
+            worker: Worker::spawn(messenger),
+            fetch,
+        });
+    }
+
+    // stop the fetch where it is, what it got so far stays on screen and
+    // in the checkpoint to resume from
+    fn cancel(&mut self) {
+        if self.run.take().is_some() {
+            self.state = State::Cancelled;
+        }
+    }
 
-        while let Some(ms) = messenger.next() {
-            self.progress = (messenger.offset, messenger.total_results);
-            ms.into_iter().for_each(|m| {
-                self.messages.push(m);
-                // we don't really care if an error happens here
-                let _ = terminal.draw(|f| draw(f, self));
-            });
-
-            if let Ok(true) = event::poll(Duration::from_secs(1)) {
-                if let Ok(Event::Key(key)) = event::read() {
-                    if key.code == KeyCode::Char('q') {
-                        reset_terminal()?;
-                        process::exit(0);
-                    }
-                }
+    // take in whatever the worker has sent since the last time, true if there was anything
+    fn update(&mut self) -> bool {
+        let mut changed = false;
+        while let Some(update) = self.run.as_ref().and_then(|run| run.worker.poll()) {
+            changed = true;
+            match update {
+                Update::Page { messages, position, fetched, total_results, wait } => {
+                    let run = match self.run.as_mut() {
+                        Some(run) => run,
+                        None => return changed,
+                    };
+                    // a fetch is worth more than its checkpoint, carry on without if saving fails
+                    let _ = run.fetch.page(position, &messages);
+                    let cached = run.fetch.cached();
+                    self.progress = (cached + fetched, cached as u32 + total_results);
+                    self.state = match wait {
+                        Some(wait) => State::Waiting(wait),
+                        None => State::Working,
+                    };
+                    self.add_messages(messages);
+                },
+                Update::Failed(e) => {
+                    self.run = None;
+                    self.state = State::Failed(e);
+                },
+                Update::Crashed => {
+                    self.run = None;
+                    self.state = State::BadInput(String::from("Fetch stopped unexpectedly, R to resume"));
+                },
+                Update::Done => self.finish(),
             }
         }
+        changed
+    }
 
+    // fold a finished fetch into the cache and export it if asked to
+    fn finish(&mut self) {
+        let run = match self.run.take() {
+            Some(run) => run,
+            None => return,
+        };
         self.state = State::Done;
+        self.fetched_at = Some(self.client.now());
+        if let Err(e) = run.fetch.finish() {
+            self.state = State::BadInput(e.to_string());
+        }
+        if let Some(path) = self.export.clone() {
+            self.export(&path);
+        }
+    }
+
+    fn meta(&self) -> export::Meta {
+        export::Meta {
+            user: self.target_user.clone(),
+            source: match self.target_loc {
+                Location::Channel => export::Source::Channel(self.target_chan.clone()),
+                Location::Guild => export::Source::Guild(self.target_guil.clone()),
+            },
+            zone: self.tally.zone(),
+            range: self.range,
+            fetched_at: self.fetched_at,
+            total_results: self.progress.1,
+            fetched: self.messages.len(),
+            zone_guess: analysis::guess_zone(self.tally.utc_hours()),
+        }
+    }
+
+    fn export(&mut self, path: &Path) {
+        self.state = match export::write(path, &self.messages, &self.meta()) {
+            Ok(()) => State::Saved(path.to_path_buf()),
+            Err(e) => State::BadInput(e.to_string()),
+        };
+    }
+
+    /// start off aimed at a target given up front, `location` being
+    /// either a channel or a guild
+    pub fn preset(&mut self, user: Option<&str>, location: Option<&str>) -> discord::Result<()> {
+        if let Some(id) = user {
+            self.set_target_user(id)?;
+        }
+        if let Some(id) = location {
+            self.set_target_chan(id)?;
+        }
         Ok(())
     }
+
+    /// pick up where a dump left off, the target user comes along with it
+    pub fn load(&mut self, messages: Vec<Message>) {
+        if let Some(m) = messages.first() {
+            if self.target_user.is_empty() {
+                self.target_user = m.author.clone();
+            }
+        }
+        self.progress = (messages.len(), messages.len() as u32);
+        self.set_messages(messages);
+    }
+
+    /// bucket hours in another timezone
+    pub fn set_zone(&mut self, zone: Zone) {
+        self.tally.set_zone(zone);
+    }
+
+    // swap out everything on screen
+    fn set_messages(&mut self, messages: Vec<Message>) {
+        self.tally.clear();
+        self.messages.clear();
+        self.add_messages(messages);
+    }
+
+    // messages only ever come in through here, to keep the tally in step
+    fn add_messages(&mut self, messages: Vec<Message>) {
+        for m in &messages {
+            self.tally.add(m);
+        }
+        self.messages.extend(messages);
+    }
+
+    // somewhere to drop an export, the working directory unless the config says otherwise
+    fn export_path(&self, ext: &str) -> PathBuf {
+        let name = format!("how-active-{}-{}.{}",
+            self.target_user.id, Utc::now().format("%Y%m%d-%H%M%S"), ext);
+        match &self.export_dir {
+            Some(dir) => dir.join(name),
+            None => PathBuf::from(name),
+        }
+    }
 }
 
 pub fn deploy(app: &mut App) -> io::Result<()> {
@@ -186,19 +483,92 @@ fn reset_terminal() -> io::Result<()> {
 }
 
 fn run<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    if app.resume {
+        app.resume = false;
+        app.resume();
+    }
+    // nothing's been drawn yet
+    let mut dirty = true;
+    let mut drawn: Option<Instant> = None;
     loop {
-        terminal.draw(|f| draw(f, app))?;
+        dirty |= app.update();
+        // however much comes in, draw at most once a frame
+        if dirty && drawn.is_none_or(|t| t.elapsed() >= FRAME) {
+            terminal.draw(|f| draw(f, app))?;
+            drawn = Some(Instant::now());
+            dirty = false;
+        }
 
+        // keep an eye on the worker while a fetch is going and catch up on a
+        // skipped frame, otherwise there's nothing to do until a key comes in
+        let timeout = match (dirty, app.run.is_some()) {
+            (true, _) => Some(drawn.map_or(Duration::ZERO, |t| FRAME.saturating_sub(t.elapsed()))),
+            (false, true) => Some(TICK),
+            (false, false) => None,
+        };
+        if let Some(timeout) = timeout {
+            if !event::poll(timeout)? {
+                continue;
+            }
+        }
+
+        dirty = true;
         if let Event::Key(key) = event::read()? {
             match app.input_mode {
                 Mode::Normal => match key.code {
+                    KeyCode::Esc if app.run.is_some() => {
+                        app.cancel();
+                    },
+                    // target and range are locked in until the run is over
+                    KeyCode::Char('u' | 'i' | 'c' | 'a' | 'r' | 'l' | 's' | 'R') if app.run.is_some() => {},
                     KeyCode::Char('u') | KeyCode::Char('i') => {
                         app.input_mode = Mode::User;
                     },
                     KeyCode::Char('c') | KeyCode::Char('a') => {
                         app.input_mode = Mode::Channel;
-                    }, KeyCode::Char('s') => { 
-                        app.start(terminal)?;
+                    },
+                    KeyCode::Char('r') => {
+                        app.input_mode = Mode::Range;
+                    },
+                    KeyCode::Char('t') => {
+                        app.input_mode = Mode::Zone;
+                    },
+                    KeyCode::Char('l') if app.targets.is_empty() => {
+                        app.state = State::BadInput(String::from("No saved targets, add some to the config"));
+                    },
+                    KeyCode::Char('l') => {
+                        if app.picked.selected().is_none() {
+                            app.picked.select(Some(0));
+                        }
+                        app.input_mode = Mode::Targets;
+                    },
+                    KeyCode::Char('e') => {
+                        app.export(&app.export_path("png"));
+                    },
+                    KeyCode::Char('E') => {
+                        app.export(&app.export_path("svg"));
+                    },
+                    KeyCode::Char('x') => {
+                        app.export(&app.export_path("csv"));
+                    },
+                    KeyCode::Char('X') => {
+                        app.export(&app.export_path("json"));
+                    },
+                    KeyCode::Char('d') => {
+                        app.export(&app.export_path("ndjson"));
+                    },
+                    KeyCode::Char('v') => {
+                        app.view = match app.view {
+                            View::Bars => View::Heatmap,
+                            View::Heatmap => View::Calendar,
+                            View::Calendar => View::Bars,
+                        };
+                    },
+                    KeyCode::Char('s') => {
+                        app.start(None);
+                    },
+                    KeyCode::Char('R') => {
+                        app.resume();
                     },
                     KeyCode::Char('q') => {
                         return Ok(())
@@ -206,16 +576,8 @@ fn run<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()>
                     _ => {},
                 },
                 Mode::User => match key.code {
-                    KeyCode::Enter => if !app.input_user.is_empty() {
-                        app.messages.clear();
-                        
-                        let user_id = app.input_user.drain(..).collect::<String>();
-                        if let Err(_) = app.set_target_user(&user_id) {
-                            app.input_user.clear();
-                            app.input_user = String::from("Invalid user id.");
-                            thread::sleep(Duration::from_secs(2));
-                            app.input_user.clear();
-                        }
+                    KeyCode::Enter if !app.input_user.is_empty() => {
+                        app.submit_user();
                         app.input_mode = Mode::Normal;
                     },
                     KeyCode::Esc => {
@@ -230,16 +592,8 @@ fn run<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()>
                     _ => {},
                 },
                 Mode::Channel => match key.code {
-                    KeyCode::Enter => if !app.input_chan.is_empty() {
-                        app.messages.clear();
-
-                        let chan_id = app.input_chan.drain(..).collect::<String>();
-                        if let Err(_) = app.set_target_chan(&chan_id) {
-                            app.input_user.clear();
-                            app.input_user = String::from("Invalid user id.");
-                            thread::sleep(Duration::from_secs(2));
-                            app.input_user.clear();
-                        }
+                    KeyCode::Enter if !app.input_chan.is_empty() => {
+                        app.submit_chan();
                         app.input_mode = Mode::Normal;
                     },
                     KeyCode::Esc => {
@@ -252,7 +606,68 @@ fn run<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()>
                         app.input_chan.push(c);
                     },
                     _ => {},
-                }
+                },
+                Mode::Range => match key.code {
+                    // an empty range goes back to all of history
+                    KeyCode::Enter => {
+                        let spec = app.input_range.drain(..).collect::<String>();
+                        app.state = match DateRange::parse_at(&spec, app.client.now()) {
+                            Ok(range) => {
+                                app.range = range;
+                                State::Idle
+                            },
+                            Err(e) => State::BadInput(e.to_string()),
+                        };
+                        app.input_mode = Mode::Normal;
+                    },
+                    KeyCode::Esc => {
+                        app.input_mode = Mode::Normal;
+                    },
+                    KeyCode::Backspace => {
+                        app.input_range.pop();
+                    },
+                    KeyCode::Char(c) => {
+                        app.input_range.push(c);
+                    },
+                    _ => {},
+                },
+                // switching zones just re-buckets what we've already got
+                Mode::Zone => match key.code {
+                    KeyCode::Enter => {
+                        let spec = app.input_zone.drain(..).collect::<String>();
+                        let spec = if spec.is_empty() { "local" } else { spec.as_str() };
+                        app.state = match spec.parse::<Zone>() {
+                            Ok(zone) => {
+                                app.set_zone(zone);
+                                State::Idle
+                            },
+                            Err(e) => State::BadInput(e.to_string()),
+                        };
+                        app.input_mode = Mode::Normal;
+                    },
+                    KeyCode::Esc => {
+                        app.input_mode = Mode::Normal;
+                    },
+                    KeyCode::Backspace => {
+                        app.input_zone.pop();
+                    },
+                    KeyCode::Char(c) => {
+                        app.input_zone.push(c);
+                    },
+                    _ => {},
+                },
+                Mode::Targets => match key.code {
+                    KeyCode::Enter => {
+                        app.input_mode = Mode::Normal;
+                        app.submit_picked();
+                    },
+                    KeyCode::Esc => {
+                        app.input_mode = Mode::Normal;
+                    },
+                    KeyCode::Up | KeyCode::Char('k') => app.pick(false),
+                    KeyCode::Down | KeyCode::Char('j') => app.pick(true),
+                    _ => {},
+                },
             }
         }
     }
@@ -272,7 +687,12 @@ fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         .split(f.size());
 
     draw_top(f, chunks[0], app);
-    draw_middle(f, chunks[1], app);
+    match app.view {
+        _ if app.input_mode == Mode::Targets => draw_targets(f, chunks[1], app),
+        View::Bars => draw_middle(f, chunks[1], app),
+        View::Heatmap => draw_heatmap(f, chunks[1], app),
+        View::Calendar => draw_calendar(f, chunks[1], app),
+    }
     draw_bottom(f, chunks[2], app);
 }
 
@@ -339,15 +759,50 @@ fn draw_top<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
             Style::default().fg(Color::LightMagenta)
             .add_modifier(Modifier::BOLD))),
         Spans::from("Target user ID:"),
-        Spans::from(target_user),
+        Spans::from(vec![target_user, created(app.target_user.created_at())]),
         Spans::from("Target channel ID:"),
-        Spans::from(target),
+        Spans::from(match app.target_loc {
+            Location::Channel => vec![target],
+            Location::Guild => vec![target, created(app.target_guil.created_at())],
+        }),
+        Spans::from(vec![
+            Span::raw("Range: "),
+            Span::styled(app.range.to_string(), Style::default().fg(Color::LightYellow)),
+        ]),
+        Spans::from(match analysis::guess_zone(app.tally.utc_hours()) {
+            Some(guess) => vec![
+                Span::raw("Likely in: "),
+                Span::styled(guess.to_string(), Style::default().fg(Color::LightGreen)),
+                Span::styled(format!(" · quiet {:02}:00–{:02}:00 UTC",
+                    guess.sleep_start, (guess.sleep_start + guess.sleep_len) % 24),
+                    Style::default().fg(Color::DarkGray)),
+            ],
+            None => vec![
+                Span::raw("Likely in: "),
+                Span::styled("not enough messages", Style::default().fg(Color::DarkGray)),
+            ],
+        }),
         Spans::from(match app.state {
             State::Idle => Span::styled("Idle", Style::default().fg(Color::Gray)),
             State::Working => Span::styled(
                 format!("Working... ({}/{})", app.progress.0, app.progress.1), 
                 Style::default().fg(Color::Yellow)),
+            State::Waiting(wait @ Wait::Indexing(_)) => Span::styled(
+                format!("{} ({}/{})", wait, app.progress.0, app.progress.1),
+                Style::default().fg(Color::LightBlue)),
+            State::Waiting(wait) => Span::styled(
+                format!("{} ({}/{})", wait, app.progress.0, app.progress.1),
+                Style::default().fg(Color::LightRed)),
             State::Done => Span::styled("Done!", Style::default().fg(Color::Green)),
+            State::Failed(ref e) => Span::styled(format!("Error: {}", e),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+            State::BadInput(ref e) => Span::styled(e.as_str(),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+            State::Saved(ref path) => Span::styled(format!("Saved {}", path.display()),
+                Style::default().fg(Color::Green)),
+            State::Cancelled => Span::styled(
+                format!("Cancelled ({}/{}), R to resume", app.progress.0, app.progress.1),
+                Style::default().fg(Color::LightRed)),
         })
     ];
 
@@ -356,35 +811,202 @@ fn draw_top<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
     f.render_widget(info, chunks[1]);
 }
 
+// dimmed creation date to trail an id with
+fn created(at: Option<DateTime<Utc>>) -> Span<'static> {
+    match at {
+        Some(t) => Span::styled(format!(" · created {}", t.format("%Y-%m-%d")),
+            Style::default().fg(Color::DarkGray)),
+        None => Span::raw(""),
+    }
+}
+
 fn draw_middle<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
-    #[rustfmt::skip]
-    let start = [
-        ("12am", 0), ("1am", 0), ("2am", 0), ("3am", 0), 
-        ("4am", 0), ("5am", 0), ("6am", 0), ("7am", 0), 
-        ("8am", 0), ("9am", 0), ("10am", 0), ("11am", 0), 
-        ("12pm", 0), ("1pm", 0), ("2pm", 0), ("3pm", 0), 
-        ("4pm", 0), ("5pm", 0), ("6pm", 0), ("7pm", 0), 
-        ("8pm", 0), ("9pm", 0), ("10pm", 0), ("11pm", 0),
-    ];
-    let data = app.messages.iter().fold(start, |mut acc, m| {
-        if let Ok(t) = DateTime::parse_from_rfc3339(m.timestamp.as_str()) {
-            acc[t.hour() as usize].1 += 1;
-        }
-        acc
-    });
+    let hours = app.tally.hours();
+    let data = HOUR_LABELS.iter()
+        .zip(hours)
+        .map(|(&label, &n)| (label, n as u64))
+        .collect::<Vec<(&str, u64)>>();
     // chart goes here
     let chart = BarChart::default()
-        .block(Block::default().title("Data").borders(Borders::ALL))
+        .block(Block::default().title(format!("Data ({})", app.tally.zone())).borders(Borders::ALL))
         .data(&data)
         .bar_width(area.width / 23 - 5)
         .bar_gap(4)
-        .bar_style(Style::default().fg(Color::LightMagenta))
-        .value_style(Style::default().bg(Color::LightMagenta).add_modifier(Modifier::ITALIC));
+        .bar_style(Style::default().fg(app.theme.accent()))
+        .value_style(Style::default().bg(app.theme.accent()).add_modifier(Modifier::ITALIC));
     f.render_widget(chart, area);
 }
 
+// the saved targets to pick from, in place of the chart
+fn draw_targets<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
+    let items = app.targets.iter()
+        .map(|t| ListItem::new(Spans::from(vec![
+            Span::styled(format!("{:<24} ", t.name), Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!("user {}", t.user)),
+            Span::styled(match (&t.channel, &t.guild) {
+                (Some(id), _) => format!(" · channel {}", id),
+                (None, Some(id)) => format!(" · guild {}", id),
+                (None, None) => String::new(),
+            }, Style::default().fg(Color::DarkGray)),
+        ])))
+        .collect::<Vec<ListItem>>();
+    let list = List::new(items)
+        .block(Block::default().title("Saved Targets").borders(Borders::ALL))
+        .highlight_style(Style::default().fg(app.theme.accent()).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, area, &mut app.picked);
+}
+
+/// how often to check on a fetch that's going
+const TICK: Duration = Duration::from_millis(100);
+/// shortest time between two redraws, ~30fps
+const FRAME: Duration = Duration::from_millis(33);
+
+const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const SHADES: [&str; 5] = [" ", "░", "▒", "▓", "█"];
+// width of the day names down the side of the heatmap and calendar
+const LABEL: usize = 5;
+
+// which of 4 shades `n` gets on a scale up to `max`, 0 for nothing at all.
+// anything at all gets at least the lightest one
+fn shade(n: u32, max: u32) -> usize {
+    match max {
+        0 => 0,
+        max => (n * 4).div_ceil(max) as usize,
+    }
+}
+
+// messages per weekday and hour, one shaded cell each
+fn draw_heatmap<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
+    let grid = app.tally.weekday_hours();
+    let max = grid.iter().flatten().copied().max().unwrap_or(0);
+
+    let block = Block::default().title(format!("Heatmap ({})", app.tally.zone())).borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    // stretch cells to fill the space, leaving room for the header and key
+    let cell = (inner.width as usize).saturating_sub(LABEL) / 24;
+    let rows = (inner.height as usize).saturating_sub(3) / 7;
+    let (cell, rows) = (cell.max(1), rows.max(1));
+
+    let mut lines = vec![Spans::from(Span::raw(
+        ["12am", "3am", "6am", "9am", "12pm", "3pm", "6pm", "9pm"].iter()
+            .fold(" ".repeat(LABEL), |acc, h| acc + &format!("{:<1$}", h, cell * 3))
+    ))];
+
+    for (day, hours) in DAYS.iter().zip(grid.iter()) {
+        for row in 0..rows {
+            let label = if row == rows / 2 { *day } else { "" };
+            let mut spans = vec![Span::raw(format!("{:<1$}", label, LABEL))];
+            spans.extend(hours.iter().map(|&n| {
+                Span::styled(SHADES[shade(n, max)].repeat(cell), Style::default().fg(app.theme.accent()))
+            }));
+            lines.push(Spans::from(spans));
+        }
+    }
+
+    lines.push(Spans::from(vec![
+        Span::raw(" ".repeat(LABEL)),
+        Span::raw("less "),
+        Span::styled(SHADES[1..].concat(), Style::default().fg(app.theme.accent())),
+        Span::raw(format!(" more · busiest hour has {} messages", max)),
+    ]));
+
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
+// one cell per day over the past year, a column per week
+fn draw_calendar<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
+    let days = app.tally.daily();
+    // finish on the last day of the range if there is one, today otherwise,
+    // going by the recording's clock on a replay
+    let end = app.range.to.map_or_else(|| app.client.now(), |to| to - chrono::Duration::nanoseconds(1));
+    let end = app.tally.zone().localize(end).date();
+
+    let block = Block::default().title(format!("Calendar ({})", app.tally.zone())).borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    // a year's worth of weeks if they fit, two columns each
+    let weeks = ((inner.width as usize).saturating_sub(LABEL) / 2).clamp(1, 53);
+    let first = end
+        - chrono::Duration::days(end.weekday().num_days_from_monday() as i64)
+        - chrono::Duration::weeks(weeks as i64 - 1);
+    let max = days.range(first..=end).map(|(_, &n)| n).max().unwrap_or(0);
+
+    // name each month above the week it starts in, as long as it fits
+    let mut months = vec![' '; weeks * 2];
+    let mut free = 0;
+    for week in 0..weeks {
+        let monday = first + chrono::Duration::weeks(week as i64);
+        let col = week * 2;
+        if (week == 0 || monday.day() <= 7) && col >= free && col + 3 <= months.len() {
+            months[col..col + 3].copy_from_slice(&monday.format("%b").to_string().chars().collect::<Vec<char>>());
+            free = col + 4;
+        }
+    }
+    let mut lines = vec![Spans::from(" ".repeat(LABEL) + &months.into_iter().collect::<String>())];
+
+    for (weekday, name) in DAYS.iter().enumerate() {
+        let mut spans = vec![Span::raw(format!("{:<1$}", name, LABEL))];
+        spans.extend((0..weeks).map(|week| {
+            let day = first + chrono::Duration::weeks(week as i64) + chrono::Duration::days(weekday as i64);
+            match days.get(&day).copied().unwrap_or(0) {
+                _ if day > end => Span::raw("  "),
+                0 => Span::styled("· ", Style::default().fg(Color::DarkGray)),
+                n => Span::styled(format!("{} ", SHADES[shade(n, max)]), Style::default().fg(app.theme.accent())),
+            }
+        }));
+        lines.push(Spans::from(spans));
+    }
+
+    let active = days.range(first..=end).count();
+    let streaks = analysis::streaks(days, end);
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(vec![
+        Span::raw(" ".repeat(LABEL)),
+        Span::raw(format!("Active on {} of the last {} days · busiest day had {} messages",
+            active, (end - first).num_days() + 1, max)),
+    ]));
+    lines.push(Spans::from(vec![
+        Span::raw(" ".repeat(LABEL)),
+        Span::raw("Longest streak: "),
+        Span::styled(streaks.active.map_or_else(|| String::from("none"), |r| r.to_string()),
+            Style::default().fg(Color::LightGreen)),
+        Span::raw(" · Longest gap: "),
+        Span::styled(streaks.gap.map_or_else(|| String::from("none"), |r| r.to_string()),
+            Style::default().fg(Color::LightRed)),
+    ]));
+
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
 fn draw_bottom<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
     let (title, help_msg) = match app.input_mode {
+        Mode::Normal if app.run.is_some() => ("Fetching",
+            Spans::from(vec![
+                Span::styled("esc", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("cancel", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                Span::raw(" | "),
+                Span::styled("t", Style::default().fg(Color::LightBlue).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("timezone", Style::default().fg(Color::LightBlue).add_modifier(Modifier::BOLD)),
+                Span::raw(" | "),
+                Span::styled("v", Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("view", Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD)),
+                Span::raw(" | "),
+                Span::styled("e/E/x/X/d", Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("export so far", Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD)),
+                Span::raw(" | "),
+                Span::styled("q", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("exit", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+            ]),
+        ),
         Mode::Normal => ("Normal",
             Spans::from(vec![
                 Span::styled("i", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
@@ -395,16 +1017,40 @@ fn draw_bottom<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
                 Span::raw(": "),
                 Span::styled("channel edit mode", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
                 Span::raw(" | "),
+                Span::styled("r", Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("date range", Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD)),
+                Span::raw(" | "),
+                Span::styled("t", Style::default().fg(Color::LightBlue).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("timezone", Style::default().fg(Color::LightBlue).add_modifier(Modifier::BOLD)),
+                Span::raw(" | "),
+                Span::styled("l", Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("saved targets", Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD)),
+                Span::raw(" | "),
+                Span::styled("v", Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("view", Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD)),
+                Span::raw(" | "),
+                Span::styled("e/E/x/X/d", Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("png/svg/csv/json/dump", Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD)),
+                Span::raw(" | "),
                 Span::styled("s", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
                 Span::raw(": "),
                 Span::styled("start", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
                 Span::raw(" | "),
+                Span::styled("R", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("resume", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+                Span::raw(" | "),
                 Span::styled("q", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
                 Span::raw(": "),
                 Span::styled("exit", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
             ]),
         ),
-        Mode::User | Mode::Channel => ("Insert",
+        Mode::User | Mode::Channel | Mode::Range | Mode::Zone => ("Insert",
             Spans::from(vec![
                 Span::styled("esc", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
                 Span::raw(": "),
@@ -415,6 +1061,21 @@ fn draw_bottom<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
                 Span::styled("confirm input", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
             ]),
         ),
+        Mode::Targets => ("Targets",
+            Spans::from(vec![
+                Span::styled("esc", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("normal mode", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                Span::raw(" | "),
+                Span::styled("↑/↓", Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("pick", Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD)),
+                Span::raw(" | "),
+                Span::styled("enter", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                Span::raw(": "),
+                Span::styled("load target", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+            ]),
+        ),
     };
 
     let chunks = Layout::default()
@@ -438,8 +1099,10 @@ fn draw_bottom<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
         .direction(Direction::Horizontal)
         .constraints(
             [
-                Constraint::Percentage(50),
-                Constraint::Percentage(50),
+                Constraint::Percentage(28),
+                Constraint::Percentage(28),
+                Constraint::Percentage(24),
+                Constraint::Percentage(20),
             ]
             .as_ref()
         )
@@ -455,8 +1118,18 @@ fn draw_bottom<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
         .alignment(Alignment::Left);
     f.render_widget(input, chunks[1]);
 
+    let input = Paragraph::new(app.input_range.as_ref())
+        .block(Block::default().title("Date Range").borders(Borders::ALL))
+        .alignment(Alignment::Left);
+    f.render_widget(input, chunks[2]);
+
+    let input = Paragraph::new(app.input_zone.as_ref())
+        .block(Block::default().title("Timezone").borders(Borders::ALL))
+        .alignment(Alignment::Left);
+    f.render_widget(input, chunks[3]);
+
     match app.input_mode {
-        Mode::Normal => {},
+        Mode::Normal | Mode::Targets => {},
         Mode::User => f.set_cursor(
             chunks[0].x + app.input_user.width() as u16 + 1,
             chunks[0].y + 1,
@@ -465,5 +1138,13 @@ fn draw_bottom<B: Backend>(f: &mut Frame<B>, area: Rect, app: &mut App) {
             chunks[1].x + app.input_chan.width() as u16 + 1,
             chunks[1].y + 1,
         ),
+        Mode::Range => f.set_cursor(
+            chunks[2].x + app.input_range.width() as u16 + 1,
+            chunks[2].y + 1,
+        ),
+        Mode::Zone => f.set_cursor(
+            chunks[3].x + app.input_zone.width() as u16 + 1,
+            chunks[3].y + 1,
+        ),
     }
 }
@@ -0,0 +1,338 @@
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::discord::{Client, Message, Messenger, Position, Snowflake};
+use crate::range::DateRange;
+
+/// fetched messages kept on disk between runs, a file per guild, channel
+/// and author, so a later run only has to ask for what's new
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+    // ignore what's there, but still write over it
+    pub refresh: bool,
+}
+
+/// whose messages and where
+#[derive(Debug, Clone)]
+pub struct Key {
+    pub guild: String,
+    // `None` when searching the whole guild
+    pub channel: Option<String>,
+    pub author: String,
+}
+
+/// what's cached for a key
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Entry {
+    // nothing's missing between here and the newest message,
+    // `None` for all of history
+    pub from: Option<DateTime<Utc>>,
+    // newest first
+    pub messages: Vec<Message>,
+}
+
+/// a fetch that hasn't finished yet, saved page by page so it can be
+/// resumed. the messages it got so far are kept next to it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Checkpoint {
+    pub range: DateRange,
+    pub position: Position,
+}
+
+/// a fetch that picks up from what's cached, notes down where it's up to
+/// after every page and folds what it got into the cache once it's done.
+/// the messenger it comes with does the fetching, on whatever thread
+pub struct Fetch {
+    cache: Option<Cache>,
+    key: Key,
+    range: DateRange,
+    entry: Entry,
+    // messages in range that came from the cache
+    cached: usize,
+    // what the messenger got, a resumed fetch's earlier pages included
+    fetched: Vec<Message>,
+    checkpointing: bool,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Json(PathBuf, serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "Cache {}: {}", path.display(), e),
+            Error::Json(path, e) => write!(f, "Cache {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Self {
+        Cache { dir, refresh: false }
+    }
+
+    /// the platform's cache directory (`$XDG_CACHE_HOME/how-active` on linux)
+    pub fn open() -> Option<Self> {
+        dirs::cache_dir().map(|dir| Cache::new(dir.join("how-active")))
+    }
+
+    fn path(&self, key: &Key) -> PathBuf {
+        self.dir
+            .join(&key.guild)
+            .join(key.channel.as_deref().unwrap_or("guild"))
+            .join(format!("{}.json", key.author))
+    }
+
+    /// what's cached for `key` that's of use for `range`. an empty entry
+    /// starting at `range.from` when there's nothing, or when what's there
+    /// doesn't go back far enough or stops short of the range, and has to
+    /// be fetched all over again
+    pub fn get(&self, key: &Key, range: &DateRange) -> Result<Entry, Error> {
+        let fresh = Entry {
+            from: range.from,
+            messages: Vec::new(),
+        };
+        if self.refresh {
+            return Ok(fresh);
+        }
+        Ok(match self.load(key)? {
+            Some(entry) if entry.covers(range.from) && entry.reaches(range.from) => entry,
+            _ => fresh,
+        })
+    }
+
+    /// whatever's cached for `key`, whether it's of use or not
+    fn load(&self, key: &Key) -> Result<Option<Entry>, Error> {
+        let path = self.path(key);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::Io(path, e)),
+        };
+        serde_json::from_reader(BufReader::new(file))
+            .map(Some)
+            .map_err(|e| Error::Json(path, e))
+    }
+
+    /// write `entry` out for `key`, replacing whatever was there
+    pub fn put(&self, key: &Key, entry: &Entry) -> Result<(), Error> {
+        write_json(&self.path(key), entry)
+    }
+
+    fn checkpoint_paths(&self) -> (PathBuf, PathBuf) {
+        (self.dir.join("checkpoint.json"), self.dir.join("checkpoint.ndjson"))
+    }
+
+    /// note down where a fetch is up to along with the page it just got,
+    /// `clear_checkpoint` first when starting a new one
+    pub fn checkpoint(&self, checkpoint: &Checkpoint, page: &[Message]) -> Result<(), Error> {
+        let (state, messages) = self.checkpoint_paths();
+        let err = |e| Error::Io(messages.clone(), e);
+        fs::create_dir_all(&self.dir).map_err(err)?;
+
+        // messages first, if we die in between the page just gets fetched again
+        let file = OpenOptions::new().create(true).append(true).open(&messages).map_err(err)?;
+        let mut out = BufWriter::new(file);
+        for m in page {
+            serde_json::to_writer(&mut out, m).map_err(|e| Error::Json(messages.clone(), e))?;
+            writeln!(out).map_err(err)?;
+        }
+        out.flush().map_err(err)?;
+        write_json(&state, checkpoint)
+    }
+
+    /// the last fetch that didn't finish and what it got, if there is one
+    pub fn resume(&self) -> Result<Option<(Checkpoint, Vec<Message>)>, Error> {
+        let (state, messages) = self.checkpoint_paths();
+        if !state.exists() {
+            return Ok(None);
+        }
+        let file = File::open(&state).map_err(|e| Error::Io(state.clone(), e))?;
+        let checkpoint = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| Error::Json(state.clone(), e))?;
+
+        let file = match File::open(&messages) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Some((checkpoint, Vec::new()))),
+            Err(e) => return Err(Error::Io(messages, e)),
+        };
+        let mut fetched = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| Error::Io(messages.clone(), e))?;
+            // dying halfway through an append leaves half a line at the end,
+            // that page gets fetched again anyway
+            if let Ok(m) = serde_json::from_str(&line) {
+                fetched.push(m);
+            }
+        }
+        Ok(Some((checkpoint, fetched)))
+    }
+
+    /// forget about the last unfinished fetch
+    pub fn clear_checkpoint(&self) -> Result<(), Error> {
+        let (state, messages) = self.checkpoint_paths();
+        for path in [state, messages] {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(Error::Io(path, e)),
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+}
+
+// write next to `path` and swap it in, so a crash can't leave half a file
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    let err = |e| Error::Io(path.to_path_buf(), e);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(err)?;
+    }
+
+    let tmp = path.with_extension("json.tmp");
+    let mut out = BufWriter::new(File::create(&tmp).map_err(err)?);
+    serde_json::to_writer(&mut out, value).map_err(|e| Error::Json(path.to_path_buf(), e))?;
+    out.flush().map_err(err)?;
+    fs::rename(&tmp, path).map_err(err)
+}
+
+impl Fetch {
+    /// get going on `key` within `range`, only asking for what's newer than
+    /// the cache has, or carry on with a fetch `resumed` from a checkpoint
+    pub fn start(client: &Client, cache: Option<Cache>, key: Key, range: DateRange,
+        resumed: Option<(Checkpoint, Vec<Message>)>) -> Result<(Fetch, Messenger), Error>
+    {
+        let entry = match &cache {
+            Some(cache) => cache.get(&key, &range)?,
+            None => Entry::default(),
+        };
+        let (messenger, fetched) = match resumed {
+            Some((checkpoint, messages)) => (Messenger::resume(client.clone(), checkpoint.position, &messages), messages),
+            None => {
+                // a new fetch takes over from whatever was left unfinished
+                if let Some(cache) = &cache {
+                    cache.clear_checkpoint()?;
+                }
+                let messenger = Messenger::new(client.clone(), key.author.clone(), key.guild.clone(), key.channel.clone())
+                    .between(range.from, range.to);
+                match entry.newest() {
+                    Some(newest) => (messenger.after(newest), Vec::new()),
+                    None => (messenger, Vec::new()),
+                }
+            },
+        };
+
+        let fetch = Fetch {
+            checkpointing: cache.is_some(),
+            cached: entry.within(&range).len(),
+            cache,
+            key,
+            range,
+            entry,
+            fetched,
+        };
+        Ok((fetch, messenger))
+    }
+
+    /// how many of the messages in range were cached already
+    pub fn cached(&self) -> usize {
+        self.cached
+    }
+
+    /// everything there is before the messenger gets its first page, what's
+    /// cached within the range and whatever a resumed fetch had got
+    pub fn so_far(&self) -> Vec<Message> {
+        let mut messages = self.entry.within(&self.range);
+        messages.extend_from_slice(&self.fetched);
+        messages
+    }
+
+    /// take in a page the messenger got, `position` being where it's up to
+    /// after it. a fetch is worth more than its checkpoint, so after one
+    /// fails to save the rest aren't kept either and the error only comes back once
+    pub fn page(&mut self, position: Position, page: &[Message]) -> Result<(), Error> {
+        self.fetched.extend_from_slice(page);
+        match &self.cache {
+            Some(cache) if self.checkpointing => {
+                let saved = cache.checkpoint(&Checkpoint { range: self.range, position }, page);
+                self.checkpointing = saved.is_ok();
+                saved
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// fold what was fetched into the cache and hand back every message in
+    /// range. only once the messenger's run out, a fetch that stopped short
+    /// would leave a hole in between
+    pub fn finish(self) -> Result<Vec<Message>, Error> {
+        let Fetch { cache, key, range, mut entry, fetched, .. } = self;
+        let cache = match cache {
+            Some(cache) => cache,
+            None => return Ok(fetched),
+        };
+        cache.clear_checkpoint()?;
+        entry.merge(fetched);
+        // a fetch that started over from a later date doesn't get to throw
+        // away what's cached from further back
+        let keep = match cache.refresh {
+            true => None,
+            false => cache.load(&key)?.filter(|old| !entry.covers(old.from)),
+        };
+        if keep.is_none() {
+            cache.put(&key, &entry)?;
+        }
+        Ok(entry.within(&range))
+    }
+}
+
+impl Entry {
+    /// whether nothing since `from` is missing
+    pub fn covers(&self, from: Option<DateTime<Utc>>) -> bool {
+        match (self.from, from) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(ours), Some(from)) => ours <= from,
+        }
+    }
+
+    /// whether the newest message is at or after `from`. fetching what's newer
+    /// than one before it would leave a hole in between
+    pub fn reaches(&self, from: Option<DateTime<Utc>>) -> bool {
+        match (self.newest(), from) {
+            (Some(newest), Some(from)) => newest.timestamp() >= from,
+            _ => true,
+        }
+    }
+
+    /// id of the newest message, fetching anything after it brings the entry up to date
+    pub fn newest(&self) -> Option<Snowflake> {
+        self.messages.iter().map(|m| m.id).max()
+    }
+
+    /// fold freshly fetched messages in, dropping ones we already have
+    pub fn merge(&mut self, messages: Vec<Message>) {
+        let mut seen = self.messages.iter().map(|m| m.id).collect::<HashSet<Snowflake>>();
+        self.messages.extend(messages.into_iter().filter(|m| seen.insert(m.id)));
+        self.messages.sort_by_key(|m| Reverse(m.id));
+    }
+
+    /// the cached messages that fall inside `range`
+    pub fn within(&self, range: &DateRange) -> Vec<Message> {
+        self.messages.iter()
+            .filter(|m| m.time().is_some_and(|t| range.contains(t)))
+            .cloned()
+            .collect()
+    }
+}
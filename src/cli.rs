@@ -0,0 +1,250 @@
+use std::{env, fs, io::{self, IsTerminal, Read}, path::PathBuf};
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand};
+
+use how_active::discord::{self, Token};
+use how_active::range::{DateRange, ParseRangeError};
+use how_active::zone::Zone;
+
+use crate::config::Config;
+use crate::headless::Format;
+
+/// Analyse when a user is active on Discord
+#[derive(Parser)]
+#[command(name = "how-active", version)]
+pub struct Cli {
+    #[command(flatten)]
+    pub token: TokenArgs,
+
+    /// Config file to read instead of the default one
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Fetch everything again instead of only what's newer than the cached messages
+    #[arg(long, global = true)]
+    pub refresh: bool,
+
+    /// Don't read or write the message cache
+    #[arg(long, global = true, conflicts_with = "refresh")]
+    pub no_cache: bool,
+
+    /// Discord API to talk to, for pointing at a stand-in server
+    #[arg(long, global = true, value_name = "URL", env = "HOW_ACTIVE_API_BASE", default_value = discord::DEFAULT_API_BASE)]
+    pub api_base: String,
+
+    /// Carry on with the last fetch that didn't finish, its target and range replace the ones given
+    #[arg(long, global = true, conflicts_with = "no_cache")]
+    pub resume: bool,
+
+    /// Save every request and response to this file, to replay later. Skips the message cache
+    #[arg(long, global = true, value_name = "FILE", conflicts_with_all = ["replay", "resume"])]
+    pub record: Option<PathBuf>,
+
+    /// Answer requests from a --record file instead of the network, no token needed
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "resume")]
+    pub replay: Option<PathBuf>,
+
+    /// What to do, the TUI when left out
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// where the token comes from, asked for with a hidden prompt when none of these has it
+#[derive(Args)]
+pub struct TokenArgs {
+    /// Read the token from this file, or from stdin when given `-`
+    #[arg(long, global = true, value_name = "FILE")]
+    pub token_file: Option<PathBuf>,
+
+    /// Environment variable to read the token from
+    #[arg(long, global = true, value_name = "VAR", default_value = "DISCORD_TOKEN")]
+    pub token_env: String,
+}
+
+impl TokenArgs {
+    /// the first token we can find: --token-file, the environment and then
+    /// a prompt if there's a terminal to ask at. never argv, where anyone on
+    /// the machine can read it off the process list
+    pub fn resolve(&self) -> io::Result<Option<Token>> {
+        if let Some(path) = &self.token_file {
+            let token = match path.to_str() {
+                Some("-") => {
+                    let mut token = String::new();
+                    io::stdin().read_to_string(&mut token)?;
+                    token
+                },
+                _ => fs::read_to_string(path)?,
+            };
+            return Ok(Token::new(&token));
+        }
+        if let Some(token) = env::var(&self.token_env).ok().and_then(|t| Token::new(&t)) {
+            return Ok(Some(token));
+        }
+        if io::stdin().is_terminal() {
+            return Ok(Token::new(&rpassword::prompt_password("Discord token: ")?));
+        }
+        Ok(None)
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Open the interactive terminal UI
+    Tui(TuiArgs),
+    /// Fetch a user's messages and write them out as NDJSON
+    Fetch(FetchArgs),
+    /// Fetch (or load) messages and print the hourly histogram
+    Report(ReportArgs),
+    /// Fetch (or load) messages and write a chart or the numbers behind it
+    Export(ExportArgs),
+}
+
+/// who to look at and where
+#[derive(Args, Default, Clone)]
+pub struct Target {
+    /// Saved target from the config file, the other flags override its parts
+    #[arg(long, value_name = "NAME")]
+    pub target: Option<String>,
+
+    /// Target user ID
+    #[arg(long, value_name = "ID")]
+    pub user: Option<String>,
+
+    /// Channel ID to search in
+    #[arg(long, value_name = "ID", conflicts_with = "guild")]
+    pub channel: Option<String>,
+
+    /// Guild ID to search in, when not narrowing it down to a channel
+    #[arg(long, value_name = "ID")]
+    pub guild: Option<String>,
+}
+
+/// which stretch of time to look at and how to read it
+#[derive(Args, Default)]
+pub struct Window {
+    /// Only look at messages sent on or after this date (2023-05-01, 2023-05, or 90d for 90 days ago)
+    #[arg(long, value_name = "DATE", value_parser = spec(DateRange::parse_from))]
+    pub from: Option<String>,
+
+    /// Only look at messages sent on or before this date
+    #[arg(long, value_name = "DATE", value_parser = spec(DateRange::parse_to))]
+    pub to: Option<String>,
+
+    /// Both ends at once, e.g. 90d, 2023-05 or 2023-01-01..2023-03-31
+    #[arg(long, value_name = "RANGE", conflicts_with_all = ["from", "to"], value_parser = spec(str::parse::<DateRange>))]
+    pub range: Option<String>,
+
+    /// Timezone to bucket hours in: local, utc, +02:00 or Europe/Prague [default: local, or tz from the config]
+    #[arg(long, value_name = "ZONE")]
+    pub tz: Option<Zone>,
+}
+
+impl Target {
+    /// fill in whatever wasn't given from the saved target named by --target,
+    /// `Err` with the name if there's no such target
+    pub fn resolve(&self, config: &Config) -> Result<Target, String> {
+        let saved = match &self.target {
+            Some(name) => config.target(name).ok_or_else(|| name.clone())?,
+            None => return Ok(self.clone()),
+        };
+        // a --channel or --guild replaces the saved location wholesale
+        let (channel, guild) = match (&self.channel, &self.guild) {
+            (None, None) => (saved.channel.clone(), saved.guild.clone()),
+            _ => (self.channel.clone(), self.guild.clone()),
+        };
+        Ok(Target {
+            target: None,
+            user: self.user.clone().or_else(|| Some(saved.user.clone())),
+            channel,
+            guild,
+        })
+    }
+}
+
+impl Window {
+    /// --tz, the config's tz or local time, in that order
+    pub fn zone(&self, config: &Config) -> Zone {
+        self.tz.or(config.tz).unwrap_or_default()
+    }
+
+    /// the range asked for, with 90d and the like counted back from `now`
+    pub fn date_range(&self, now: DateTime<Utc>) -> Result<DateRange, ParseRangeError> {
+        // --from and --to are the two ends of a --range, and get the same checks
+        let range = match &self.range {
+            Some(range) => range.clone(),
+            None => format!("{}..{}", self.from.as_deref().unwrap_or(""), self.to.as_deref().unwrap_or("")),
+        };
+        DateRange::parse_at(&range, now)
+    }
+}
+
+// check a range spec as the args are parsed, but keep it as written so a
+// replay can count 90d back from when it was recorded
+fn spec<T>(parse: fn(&str) -> Result<T, ParseRangeError>) -> impl Fn(&str) -> Result<String, ParseRangeError> + Clone {
+    move |s| parse(s).map(|_| s.to_string())
+}
+
+#[derive(Args, Default)]
+pub struct TuiArgs {
+    #[command(flatten)]
+    pub target: Target,
+
+    #[command(flatten)]
+    pub window: Window,
+
+    /// Write the chart (.png, .svg), the numbers (.csv, .json) or the messages (.ndjson)
+    /// to this file whenever a run finishes
+    #[arg(long, value_name = "FILE")]
+    pub export: Option<PathBuf>,
+
+    /// Start off with the messages from an .ndjson dump
+    #[arg(long, value_name = "FILE")]
+    pub load: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct FetchArgs {
+    #[command(flatten)]
+    pub target: Target,
+
+    #[command(flatten)]
+    pub window: Window,
+
+    /// File to write the messages to, stdout when left out
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct ReportArgs {
+    #[command(flatten)]
+    pub target: Target,
+
+    #[command(flatten)]
+    pub window: Window,
+
+    /// Read messages from an .ndjson dump instead of fetching them
+    #[arg(long, value_name = "FILE")]
+    pub load: Option<PathBuf>,
+
+    /// How to print the histogram
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    pub format: Format,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub target: Target,
+
+    #[command(flatten)]
+    pub window: Window,
+
+    /// Read messages from an .ndjson dump instead of fetching them
+    #[arg(long, value_name = "FILE")]
+    pub load: Option<PathBuf>,
+
+    /// File to write, its extension picks the format: .png, .svg, .csv, .json or .ndjson
+    #[arg(short, long, value_name = "FILE")]
+    pub output: PathBuf,
+}
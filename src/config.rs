@@ -0,0 +1,119 @@
+use std::{fmt, fs, io, path::{Path, PathBuf}};
+use serde::Deserialize;
+use tui::style::Color;
+
+use how_active::zone::Zone;
+
+/// what's kept between sessions, read from `config.toml` in the
+/// `how-active` config directory (`$XDG_CONFIG_HOME/how-active/` on linux)
+///
+/// ```toml
+/// tz = "Europe/Prague"
+/// theme = "ocean"
+/// export_dir = "~/exports"
+///
+/// [[targets]]
+/// name = "alice in general"
+/// user = "123456789012345678"
+/// channel = "234567890123456789"
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // timezone to use when --tz isn't given
+    pub tz: Option<Zone>,
+    pub theme: Theme,
+    // where the tui drops its exports, the working directory otherwise
+    pub export_dir: Option<PathBuf>,
+    pub targets: Vec<Target>,
+}
+
+/// a user and where to look for them, saved under a name
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Target {
+    pub name: String,
+    pub user: String,
+    pub channel: Option<String>,
+    pub guild: Option<String>,
+}
+
+impl Target {
+    /// the channel if there is one, the guild otherwise
+    pub fn location(&self) -> Option<&str> {
+        self.channel.as_deref().or(self.guild.as_deref())
+    }
+}
+
+/// colours the charts are drawn in
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Magenta,
+    Ocean,
+    Forest,
+    Mono,
+}
+
+impl Theme {
+    /// the colour bars and cells are filled with
+    pub fn accent(&self) -> Color {
+        match self {
+            Theme::Magenta => Color::LightMagenta,
+            Theme::Ocean => Color::LightCyan,
+            Theme::Forest => Color::LightGreen,
+            Theme::Mono => Color::White,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "Couldn't read {}: {}", path.display(), e),
+            Error::Parse(path, e) => write!(f, "Bad config in {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl Config {
+    /// where the config lives unless told otherwise
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("how-active").join("config.toml"))
+    }
+
+    /// read the config at `path`, or at the default path when `None`.
+    /// a missing default config is just an empty one
+    pub fn load(path: Option<&Path>) -> Result<Self, Error> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Config::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Config::default()),
+            },
+        };
+        let text = fs::read_to_string(&path).map_err(|e| Error::Io(path.clone(), e))?;
+        toml::from_str(&text).map_err(|e| Error::Parse(path, e))
+    }
+
+    /// look a saved target up by name
+    pub fn target(&self, name: &str) -> Option<&Target> {
+        self.targets.iter().find(|t| t.name == name)
+    }
+
+    /// `export_dir` with a leading `~` expanded
+    pub fn export_dir(&self) -> Option<PathBuf> {
+        let dir = self.export_dir.as_ref()?;
+        match (dir.strip_prefix("~"), dirs::home_dir()) {
+            (Ok(rest), Some(home)) => Some(home.join(rest)),
+            _ => Some(dir.clone()),
+        }
+    }
+}
@@ -1,13 +1,290 @@
-use std::fmt;
+use std::{collections::HashSet, error, fmt, num::ParseIntError, result, str::FromStr, sync::Arc, thread, time::{Duration, Instant}};
+use chrono::{DateTime, TimeZone, Utc};
 use reqwest::{
     self,
-    blocking::Client,
-    header, 
+    blocking,
+    header::{self, HeaderMap, HeaderValue},
+    StatusCode,
 };
 use serde::{Deserialize, Serialize};
 
+use crate::tape::{self, Exchange, Tape};
+
+/// where requests go unless `Client::with_api_base` says otherwise
+pub const DEFAULT_API_BASE: &str = "https://discord.com/api/v9";
+
+/// who we are to discord and where its api lives, handed to everything
+/// that makes a request. clones share the tape, if there is one
+#[derive(Clone)]
+pub struct Client {
+    token: Token,
+    base: String,
+    tape: Option<Arc<Tape>>,
+}
+
+impl Client {
+    pub fn new(token: Token) -> Self {
+        Client {
+            token,
+            base: DEFAULT_API_BASE.to_string(),
+            tape: None,
+        }
+    }
+
+    /// send requests to another server, a stand-in for testing say
+    pub fn with_api_base(mut self, url: &str) -> Self {
+        self.base = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// record every request and response, or answer them from a
+    /// recording instead of the network
+    pub fn with_tape(mut self, tape: Tape) -> Self {
+        self.tape = Some(Arc::new(tape));
+        self
+    }
+
+    /// the time as far as the requests are concerned. with a tape going it's
+    /// when the latest response came in, or the recording started before
+    /// then, so a replay gets the same time back
+    pub fn now(&self) -> DateTime<Utc> {
+        self.tape.as_deref().and_then(Tape::last).unwrap_or_else(Utc::now)
+    }
+
+    fn replaying(&self) -> bool {
+        self.tape.as_deref().is_some_and(Tape::replaying)
+    }
+
+    // make a request to `path` under the api base, or have the tape answer it
+    fn send(&self, http: &blocking::Client, path: &str) -> Result<Exchange> {
+        if let Some(replayed) = self.replayed(path) {
+            return replayed;
+        }
+        let resp = http.get(format!("{}{}", self.base, path))
+            .header(header::AUTHORIZATION, self.token.header())
+            .header(header::USER_AGENT, AGENT)
+            .send()
+            .map_err(Error::Network)?;
+        let (status, headers) = (resp.status(), resp.headers().clone());
+        self.recorded(path, status, &headers, resp.text().map_err(Error::Network)?)
+    }
+
+    // same as `send`, without blocking
+    #[cfg(feature = "async")]
+    async fn send_async(&self, http: &reqwest::Client, path: &str) -> Result<Exchange> {
+        if let Some(replayed) = self.replayed(path) {
+            return replayed;
+        }
+        let resp = http.get(format!("{}{}", self.base, path))
+            .header(header::AUTHORIZATION, self.token.header())
+            .header(header::USER_AGENT, AGENT)
+            .send()
+            .await
+            .map_err(Error::Network)?;
+        let (status, headers) = (resp.status(), resp.headers().clone());
+        self.recorded_async(path, status, &headers, resp.text().await.map_err(Error::Network)?).await
+    }
+
+    // the tape's answer to `path` when we're replaying one
+    fn replayed(&self, path: &str) -> Option<Result<Exchange>> {
+        self.tape.as_deref()
+            .filter(|t| t.replaying())
+            .map(|t| t.play(path).map_err(Error::Tape))
+    }
+
+    // a response off the wire, written down if we're recording
+    fn recorded(&self, path: &str, status: StatusCode, headers: &HeaderMap, body: String) -> Result<Exchange> {
+        let exchange = exchange(path, status, headers, body);
+        if let Some(tape) = &self.tape {
+            tape.write(&exchange).map_err(Error::Tape)?;
+        }
+        Ok(exchange)
+    }
+
+    // same as `recorded`, with the write to the tape off on a blocking
+    // thread so it doesn't hold up the runtime
+    #[cfg(feature = "async")]
+    async fn recorded_async(&self, path: &str, status: StatusCode, headers: &HeaderMap, body: String) -> Result<Exchange> {
+        let exchange = exchange(path, status, headers, body);
+        if let Some(tape) = self.tape.clone() {
+            let written = exchange.clone();
+            tokio::task::spawn_blocking(move || tape.write(&written))
+                .await
+                .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+                .map_err(Error::Tape)?;
+        }
+        Ok(exchange)
+    }
+}
+
+// a response as it goes on the tape, keeping only the headers of use to us
+fn exchange(path: &str, status: StatusCode, headers: &HeaderMap, body: String) -> Exchange {
+    Exchange {
+        path: path.to_string(),
+        at: Utc::now(),
+        status: status.as_u16(),
+        headers: headers.iter()
+            .filter(|(name, _)| Exchange::keeps(name.as_str()))
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body,
+    }
+}
+
 pub const AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) discord/0.0.21 Chrome/94.0.4606.81 Electron/15.5.7 Safari/537.36";
 
+/// everything that can go wrong talking to discord
+#[derive(Debug)]
+pub enum Error {
+    // token was rejected
+    Unauthorized,
+    // token is fine but can't see the thing (e.g. a channel we have no access to)
+    Forbidden,
+    NotFound,
+    RateLimited(Duration),
+    // any other status we didn't expect, server errors included
+    Http(StatusCode),
+    Decode(serde_json::Error),
+    Network(reqwest::Error),
+    // a messenger without a user or a guild to search
+    NoTarget,
+    // couldn't record, or the recording we're replaying doesn't have it
+    Tape(tape::Error),
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unauthorized => write!(f, "Invalid auth token"),
+            Error::Forbidden => write!(f, "Missing access"),
+            Error::NotFound => write!(f, "Not found"),
+            Error::RateLimited(d) => write!(f, "Rate limited for {:.1}s", d.as_secs_f32()),
+            Error::Http(status) => write!(f, "Unexpected response: {}", status),
+            Error::Decode(e) => write!(f, "Couldn't decode response: {}", e),
+            Error::Network(e) => write!(f, "Network error: {}", e),
+            Error::NoTarget => write!(f, "Need a user and a guild to search"),
+            Error::Tape(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Decode(e) => Some(e),
+            Error::Network(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// an auth token, kept out of `Debug` output and sent as a sensitive header
+/// so it doesn't end up in logs or error messages
+#[derive(Clone, PartialEq, Eq)]
+pub struct Token(String);
+
+impl Token {
+    /// `None` for a blank token, surrounding whitespace (a trailing newline
+    /// from a file, say) is dropped
+    pub fn new(token: &str) -> Option<Self> {
+        let token = token.trim();
+        (!token.is_empty()).then(|| Token(token.to_string()))
+    }
+
+    fn header(&self) -> HeaderValue {
+        // a token that can't be a header can't be valid either, sending
+        // nothing gets us a plain 401
+        let mut value = HeaderValue::from_str(&self.0).unwrap_or(HeaderValue::from_static(""));
+        value.set_sensitive(true);
+        value
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Token(***)")
+    }
+}
+
+// turn a non-2xx response into the matching error
+fn check(resp: Exchange) -> Result<Exchange> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+    Err(match status {
+        StatusCode::UNAUTHORIZED => Error::Unauthorized,
+        StatusCode::FORBIDDEN => Error::Forbidden,
+        StatusCode::NOT_FOUND => Error::NotFound,
+        StatusCode::TOO_MANY_REQUESTS => Error::RateLimited(retry_after(resp)),
+        _ => Error::Http(status),
+    })
+}
+
+// how long a 429 asks us to wait: the precise header first,
+// then the rounded one, then the body
+fn retry_after(resp: Exchange) -> Duration {
+    header_secs(&resp, "x-ratelimit-reset-after")
+        .or_else(|| header_secs(&resp, header::RETRY_AFTER.as_str()))
+        .or_else(|| resp.json::<RateLimited>().ok()
+            .map(|r| Duration::from_secs_f64(r.retry_after.max(0.0))))
+        .unwrap_or(Duration::from_secs(1))
+}
+
+/// discord's epoch, the first moment of 2015, in ms since the unix epoch
+pub const DISCORD_EPOCH: u64 = 1_420_070_400_000;
+
+/// a discord id, which carries its creation time in the top 42 bits
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(into = "String", try_from = "String")]
+pub struct Snowflake(pub u64);
+
+impl Snowflake {
+    /// when the id was created
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        let ms = (self.0 >> 22) + DISCORD_EPOCH;
+        Utc.timestamp_millis_opt(ms as i64).unwrap()
+    }
+
+    /// lowest id that could have been created at `time`, handy as a
+    /// search bound. anything before the discord epoch clamps to 0, and
+    /// anything past what 42 bits of ms reach (around 2154) to the last id
+    pub fn from_datetime(time: DateTime<Utc>) -> Self {
+        let ms = (time.timestamp_millis().max(0) as u64).saturating_sub(DISCORD_EPOCH);
+        Snowflake(ms.min(u64::MAX >> 22) << 22)
+    }
+}
+
+impl FromStr for Snowflake {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        s.parse::<u64>().map(Snowflake)
+    }
+}
+
+impl TryFrom<String> for Snowflake {
+    type Error = ParseIntError;
+
+    fn try_from(s: String) -> result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Snowflake> for String {
+    fn from(s: Snowflake) -> Self {
+        s.to_string()
+    }
+}
+
+impl fmt::Display for Snowflake {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 macro_rules! make_object {
     (
         $name:ident,
@@ -17,18 +294,16 @@ macro_rules! make_object {
         ($format:literal, $($value:ident),+),
         $($vis:vis $attribute:ident: $type:ty = $default:expr),+,
     ) => {
-        #[derive(Serialize, Deserialize, Debug)]
+        #[derive(Serialize, Deserialize, Debug, Clone)]
         pub struct $name {
             $($vis $attribute: $type),*
         }
 
-        pub fn $func_name(id: &str, token: &str) -> reqwest::Result<$name> {
-            Client::new().get(format!("https://discord.com/api/v9/{}/{}", $endpoint, id))
-                .header(header::AUTHORIZATION, token)
-                .header(header::USER_AGENT, AGENT)
-                .send()?
-                .error_for_status()?
+        pub fn $func_name(id: &str, client: &Client) -> Result<$name> {
+            let resp = client.send(&blocking::Client::new(), &format!("/{}/{}", $endpoint, id))?;
+            check(resp)?
                 .json::<$name>()
+                .map_err(Error::Decode)
         }
 
         impl fmt::Display for $name {
@@ -57,15 +332,22 @@ make_object! {
     User, get_user, id, "users", 
     ("{}#{} ({})", username, discriminator, id),
     pub id: String = String::new(),
-    username: String = String::new(),
-    discriminator: String = String::new(),
+    pub username: String = String::new(),
+    pub discriminator: String = String::new(),
+}
+
+impl User {
+    /// when the account was made, read off its id
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.id.parse::<Snowflake>().ok().map(|s| s.timestamp())
+    }
 }
 
 make_object! {
     Channel, get_channel, id, "channels",
     ("{} ({})", name, id),
     pub id: String = String::new(),
-    name: String = String::new(),
+    pub name: String = String::new(),
     pub guild_id: String = String::new(),
 }
 
@@ -73,7 +355,72 @@ make_object! {
     Guild, get_guild, id, "guilds",
     ("{} ({})", name, id),
     pub id: String = String::new(),
-    name: String = String::new(),
+    pub name: String = String::new(),
+}
+
+impl Guild {
+    /// when the guild was made, read off its id
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.id.parse::<Snowflake>().ok().map(|s| s.timestamp())
+    }
+}
+
+/// longest we'll hold off for between retries of a failing server
+const MAX_BACKOFF: Duration = Duration::from_secs(64);
+/// consecutive server errors tolerated before giving up
+const MAX_RETRIES: u32 = 6;
+
+/// reason the messenger is holding off on its next request
+#[derive(Debug, Clone, Copy)]
+pub enum Wait {
+    RateLimit(Duration),
+    ServerError(Duration),
+    // the guild's search index is still being built
+    Indexing(Duration),
+}
+
+impl Wait {
+    pub fn duration(&self) -> Duration {
+        match *self {
+            Wait::RateLimit(d) | Wait::ServerError(d) | Wait::Indexing(d) => d,
+        }
+    }
+}
+
+impl fmt::Display for Wait {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Wait::RateLimit(d) => write!(f, "Rate limited, retrying in {:.1}s", d.as_secs_f32()),
+            Wait::ServerError(d) => write!(f, "Server error, retrying in {:.1}s", d.as_secs_f32()),
+            Wait::Indexing(d) => write!(f, "Indexing… retrying in {:.1}s", d.as_secs_f32()),
+        }
+    }
+}
+
+// parse a header holding a (possibly fractional) number of seconds
+fn header_secs(resp: &Exchange, name: &str) -> Option<Duration> {
+    resp.header(name)?
+        .parse::<f64>().ok()
+        .filter(|s| s.is_finite() && *s >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+/// results discord returns per search page
+const PAGE_SIZE: usize = 25;
+/// furthest the search endpoint lets us page with `offset`
+const MAX_OFFSET: usize = 5000;
+
+/// how far a messenger has got, enough to carry on from later
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Position {
+    pub user: String,
+    pub guild: String,
+    pub channel: Option<String>,
+    pub offset: usize,
+    pub total_results: u32,
+    pub max_id: Option<Snowflake>,
+    pub min_id: Option<Snowflake>,
+    pub oldest: Option<Snowflake>,
 }
 
 /// struct that implements iterator to incrementally
@@ -82,78 +429,358 @@ pub struct Messenger {
     user: String,
     guild: String,
     channel: String,
-    pub offset: usize,
-    pub total_results: u32,
-    token: String,
+    // offset into the current window
+    offset: usize,
+    total_results: u32,
+    // unique messages yielded so far, across all windows
+    fetched: usize,
+    // upper bound of the current window, exclusive, `None` for the newest
+    max_id: Option<Snowflake>,
+    // lower bound of every window, exclusive
+    min_id: Option<Snowflake>,
+    // oldest message id seen so far, where the next window starts
+    oldest: Option<Snowflake>,
+    seen: HashSet<Snowflake>,
+    // set while we're holding off on the next request
+    wait: Option<Wait>,
+    until: Option<Instant>,
+    retries: u32,
+    done: bool,
     client: Client,
+    // made on first use, an async messenger never needs one
+    http: Option<blocking::Client>,
 }
 
 impl Messenger {
-    pub fn new(token: String, user: String, guild: String, channel: Option<String>) -> Self {
+    pub fn new(client: Client, user: String, guild: String, channel: Option<String>) -> Self {
         Messenger {
-            token,
+            client,
             user,
             guild,
-            channel: channel.unwrap_or_else(|| String::new()),
+            channel: channel.unwrap_or_default(),
             offset: 0,
             total_results: 0,
-            client: Client::new(),
+            fetched: 0,
+            max_id: None,
+            min_id: None,
+            oldest: None,
+            seen: HashSet::new(),
+            wait: None,
+            until: None,
+            retries: 0,
+            done: false,
+            http: None,
+        }
+    }
+
+    /// only grab messages sent within `from..to`, either end may be open
+    pub fn between(mut self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Self {
+        // min_id is exclusive, step back one so messages right on `from` count
+        self.min_id = from.map(|t| Snowflake(Snowflake::from_datetime(t).0.saturating_sub(1)));
+        self.max_id = to.map(Snowflake::from_datetime);
+        self
+    }
+
+    /// only fetch messages newer than `id`, on top of any range
+    pub fn after(mut self, id: Snowflake) -> Self {
+        self.min_id = self.min_id.max(Some(id));
+        // nothing can be both newer than `id` and older than the range's end
+        if self.max_id.is_some_and(|max| id >= max) {
+            self.done = true;
         }
+        self
+    }
+
+    /// pick up where `position` left off, `messages` being everything
+    /// fetched before it so they don't get yielded twice
+    pub fn resume(client: Client, position: Position, messages: &[Message]) -> Self {
+        let mut messenger = Messenger::new(client, position.user, position.guild, position.channel);
+        messenger.offset = position.offset;
+        messenger.total_results = position.total_results;
+        messenger.max_id = position.max_id;
+        messenger.min_id = position.min_id;
+        messenger.oldest = position.oldest;
+        messenger.seen = messages.iter().map(|m| m.id).collect();
+        messenger.fetched = messenger.seen.len();
+        messenger
+    }
+
+    /// where we're up to, to `resume` from
+    pub fn position(&self) -> Position {
+        Position {
+            user: self.user.clone(),
+            guild: self.guild.clone(),
+            channel: Some(self.channel.clone()).filter(|c| !c.is_empty()),
+            offset: self.offset,
+            total_results: self.total_results,
+            max_id: self.max_id,
+            min_id: self.min_id,
+            oldest: self.oldest,
+        }
+    }
+
+    /// offset into the current search window
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// how many messages the search says there are, below the current
+    /// window once it's had to slide
+    pub fn total_results(&self) -> u32 {
+        self.total_results
+    }
+
+    /// unique messages yielded so far
+    pub fn fetched(&self) -> usize {
+        self.fetched
+    }
+
+    /// why the messenger's holding off on its next request, if it is
+    pub fn wait(&self) -> Option<Wait> {
+        self.wait
+    }
+
+    /// time left until the next request goes out
+    pub fn remaining(&self) -> Option<Duration> {
+        self.until.map(|t| t.saturating_duration_since(Instant::now()))
+    }
+
+    fn hold_off(&mut self, wait: Wait) {
+        self.wait = Some(wait);
+        // a replay has nobody on the other end to wait for
+        self.until = Some(Instant::now() + if self.client.replaying() { Duration::ZERO } else { wait.duration() });
+    }
+
+    fn search_path(&self) -> String {
+        format!("/guilds/{}/messages/search?author_id={}&sort_by=timestamp&sort_order=desc{}{}{}{}",
+            self.guild, self.user,
+            if !self.channel.is_empty() {
+                format!("&channel_id={}", self.channel)
+            } else { String::from("") },
+            if let Some(max_id) = self.max_id {
+                format!("&max_id={}", max_id)
+            } else { String::from("") },
+            if let Some(min_id) = self.min_id {
+                format!("&min_id={}", min_id)
+            } else { String::from("") },
+            if self.offset != 0 {
+                format!("&offset={}", self.offset)
+            } else { String::from("") })
+    }
+
+    // grab the next page, `None` once there's nothing left
+    fn fetch(&mut self) -> Result<Option<Vec<Message>>> {
+        if let Some(left) = self.remaining() {
+            thread::sleep(left);
+        }
+        let path = match self.next_path()? {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let resp = self.client.send(self.http.get_or_insert_with(blocking::Client::new), &path)?;
+        self.take(resp)
+    }
+
+    /// `fetch` without blocking, for the async messenger. the caller sits
+    /// out `remaining` first
+    #[cfg(feature = "async")]
+    pub(crate) async fn fetch_async(&mut self, http: &reqwest::Client) -> Result<Option<Vec<Message>>> {
+        let path = match self.next_path()? {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let resp = self.client.send_async(http, &path).await?;
+        self.take(resp)
+    }
+
+    // what to ask for next, `None` when there's nothing left to ask for
+    fn next_path(&mut self) -> Result<Option<String>> {
+        if self.guild.is_empty() || self.user.is_empty() {
+            return Err(Error::NoTarget);
+        }
+        self.wait = None;
+        self.until = None;
+
+        // search won't page past MAX_OFFSET, so slide the window down to
+        // end at the oldest message we've got and page from its top again
+        if self.offset + PAGE_SIZE > MAX_OFFSET {
+            match self.oldest {
+                Some(oldest) if self.max_id != Some(oldest) => {
+                    self.max_id = Some(oldest);
+                    self.offset = 0;
+                },
+                // window didn't move, we'd only get the same page again
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(self.search_path()))
+    }
+
+    // make what we got for `next_path` into a page
+    fn take(&mut self, resp: Exchange) -> Result<Option<Vec<Message>>> {
+        let status = resp.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            self.hold_off(Wait::RateLimit(retry_after(resp)));
+            return Ok(Some(Vec::new()));
+        }
+
+        // discord answers 202 until it has indexed the guild for search
+        if status == StatusCode::ACCEPTED {
+            let retry = resp.json::<RateLimited>().ok()
+                .map(|r| Duration::from_secs_f64(r.retry_after.max(0.0)))
+                .unwrap_or(Duration::from_secs(2));
+            self.hold_off(Wait::Indexing(retry));
+            return Ok(Some(Vec::new()));
+        }
+
+        if status.is_server_error() {
+            if self.retries >= MAX_RETRIES {
+                return Err(Error::Http(status));
+            }
+            let backoff = Duration::from_secs(1 << self.retries).min(MAX_BACKOFF);
+            self.retries += 1;
+            self.hold_off(Wait::ServerError(backoff));
+            return Ok(Some(Vec::new()));
+        }
+        self.retries = 0;
+
+        let resp = check(resp)?;
+
+        // bucket's empty, wait for it to refill before asking again
+        let remaining = resp.header("x-ratelimit-remaining")
+            .and_then(|v| v.parse::<u32>().ok());
+        if remaining == Some(0) {
+            if let Some(reset) = header_secs(&resp, "x-ratelimit-reset-after") {
+                self.hold_off(Wait::RateLimit(reset));
+            }
+        }
+
+        let v = resp.json::<MessageSearch>().map_err(Error::Decode)?;
+        // later windows only count what's left below them
+        if self.oldest.is_none() {
+            self.total_results = v.total_results;
+        }
+        let page = v.messages.into_iter().flatten().collect::<Vec<Message>>();
+        self.offset += page.len();
+        if page.is_empty() {
+            return Ok(None);
+        }
+        if self.offset >= v.total_results as usize {
+            self.done = true;
+        }
+
+        self.oldest = page.iter()
+            .map(|m| m.id)
+            .chain(self.oldest)
+            .min();
+        // windows can overlap on their edges
+        let guild_id = self.guild.parse::<Snowflake>().ok();
+        let messages = page.into_iter()
+            .filter(|m| self.seen.insert(m.id))
+            .map(|m| Message { guild_id, ..m })
+            .collect::<Vec<Message>>();
+        self.fetched += messages.len();
+        Ok(Some(messages))
     }
 }
 
 impl Iterator for Messenger {
-    type Item = Vec<Message>;
+    type Item = Result<Vec<Message>>;
 
+    /// yields an empty page whenever the messenger has to hold off,
+    /// check `wait()` to tell why. stops after the first error
     fn next(&mut self) -> Option<Self::Item> {
-        assert!(!self.guild.is_empty());
-        assert!(!self.user.is_empty());
-        if let Ok(resp) = self.client.get(format!("https://discord.com/api/v9/guilds/{}/messages/search?author_id={}{}{}",
-                self.guild, self.user, 
-                if !self.channel.is_empty() {
-                    format!("&channel_id={}", self.channel)
-                } else { String::from("") }, 
-                if self.offset != 0 {
-                    format!("&offset={}", self.offset)
-                } else { String::from("") }))
-            .header(header::AUTHORIZATION, &self.token)
-            .header(header::USER_AGENT, AGENT)
-            .send() {
-                if let Ok(resp) = resp.error_for_status() {
-                    match resp.json::<MessageSearch>() {
-                        Ok(v) => {
-                            self.total_results = v.total_results;
-                            let messages = v.messages.into_iter().flatten().collect::<Vec<Message>>();
-                            self.offset += messages.len();
-                            Some(messages)
-                        },
-                        Err(_) => None,
-                    }
-                } else {
-                    None
-                }
-            } else {
+        if !self.ready() {
+            return None;
+        }
+        let fetched = self.fetch();
+        self.settle(fetched)
+    }
+}
+
+impl Messenger {
+    // whether there's anything left to fetch
+    pub(crate) fn ready(&self) -> bool {
+        !self.done
+    }
+
+    // turn what a fetch got into what to yield, finishing up after the last page
+    pub(crate) fn settle(&mut self, fetched: Result<Option<Vec<Message>>>) -> Option<Result<Vec<Message>>> {
+        match fetched {
+            Ok(Some(ms)) => Some(Ok(ms)),
+            Ok(None) => {
+                self.done = true;
                 None
-            }
+            },
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            },
+        }
     }
 }
 
+// body of both 429 and "index not ready" 202 responses
+#[derive(Serialize, Deserialize, Debug)]
+struct RateLimited {
+    retry_after: f64,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct MessageSearch {
     total_results: u32,
     messages: Vec<Vec<Message>>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Message {
-    id: String,
-    author: User,
+    pub id: Snowflake,
+    pub author: User,
     pub timestamp: String,
-    edited_timestamp: Option<String>,
-    content: String,
+    pub edited_timestamp: Option<String>,
+    pub content: String,
+    #[serde(default)]
+    pub channel_id: Snowflake,
+    // search results leave this out, the messenger fills it in
+    #[serde(default)]
+    pub guild_id: Option<Snowflake>,
+}
+
+impl Message {
+    /// when the message was sent
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.timestamp).ok()
+            .map(|t| t.with_timezone(&Utc))
+    }
 }
 
 impl fmt::Display for Message { fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}#{}: {}", self.author.username, self.author.discriminator, self.content)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snowflakes_round_trip_through_time() {
+        let t = Utc.with_ymd_and_hms(2023, 5, 20, 12, 34, 56).unwrap() + chrono::Duration::milliseconds(789);
+        assert_eq!(Snowflake::from_datetime(t).timestamp(), t);
+        // a real id, made at 2016-04-30 11:18:25.796
+        let id = Snowflake(175928847299117063);
+        assert_eq!(Snowflake::from_datetime(id.timestamp()).timestamp(), id.timestamp());
+        assert_eq!(id.timestamp().timestamp_millis(), 1462015105796);
+    }
+
+    #[test]
+    fn snowflakes_clamp_out_of_range_times() {
+        let before = Utc.with_ymd_and_hms(2010, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(Snowflake::from_datetime(before), Snowflake(0));
+
+        let after = Utc.with_ymd_and_hms(2300, 1, 1, 0, 0, 0).unwrap();
+        let last = Snowflake::from_datetime(after);
+        assert_eq!(last, Snowflake((u64::MAX >> 22) << 22));
+        assert!(last.timestamp() > Utc.with_ymd_and_hms(2150, 1, 1, 0, 0, 0).unwrap());
+    }
+}
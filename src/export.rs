@@ -0,0 +1,232 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use plotters::{
+    prelude::*,
+    coord::Shift,
+};
+use serde::Serialize;
+
+use crate::analysis::{Tally, ZoneGuess, HOUR_LABELS};
+use crate::discord::{Channel, Guild, Message, User};
+use crate::range::DateRange;
+use crate::zone::Zone;
+
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 720;
+const BAR: RGBColor = RGBColor(0xd1, 0x5f, 0xd1);
+
+/// where the messages were searched for
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Channel(Channel),
+    Guild(Guild),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Channel(c) => write!(f, "{}", c),
+            Source::Guild(g) => write!(f, "{}", g),
+        }
+    }
+}
+
+/// who and what an export covers
+#[derive(Serialize, Debug)]
+pub struct Meta {
+    pub user: User,
+    pub source: Source,
+    pub zone: Zone,
+    pub range: DateRange,
+    pub fetched_at: Option<DateTime<Utc>>,
+    // what the search said there was against what we actually got
+    pub total_results: u32,
+    pub fetched: usize,
+    pub zone_guess: Option<ZoneGuess>,
+}
+
+/// everything we aggregate, as written to csv and json
+#[derive(Serialize, Debug)]
+pub struct Report<'a> {
+    pub meta: &'a Meta,
+    // what the zone's offset from UTC was as of the fetch, "+02:00"
+    pub utc_offset: String,
+    pub hours: [u32; 24],
+    // monday first
+    pub weekday_hours: [[u32; 24]; 7],
+    pub daily: BTreeMap<NaiveDate, u32>,
+}
+
+impl<'a> Report<'a> {
+    pub fn new(messages: &[Message], meta: &'a Meta) -> Self {
+        let tally = Tally::of(messages, meta.zone);
+        Report {
+            meta,
+            utc_offset: meta.zone.offset(meta.fetched_at.unwrap_or_else(Utc::now)).to_string(),
+            hours: *tally.hours(),
+            weekday_hours: *tally.weekday_hours(),
+            daily: tally.daily().clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    // extension we don't know how to write
+    Format(String),
+    Draw(String),
+    Io(io::Error),
+    Json(serde_json::Error),
+    // a line of a message dump that isn't a message
+    Line(usize, serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Format(ext) => write!(f, "Can't export to `{}`, use .png, .svg, .csv, .json or .ndjson", ext),
+            Error::Draw(e) => write!(f, "Couldn't draw chart: {}", e),
+            Error::Io(e) => write!(f, "Couldn't write export: {}", e),
+            Error::Json(e) => write!(f, "Couldn't write export: {}", e),
+            Error::Line(n, e) => write!(f, "Bad message on line {}: {}", n, e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+/// write `messages` out to `path`, as a chart (png, svg), the aggregated
+/// numbers (csv, json) or the messages themselves (ndjson, jsonl) going
+/// by its extension
+pub fn write(path: &Path, messages: &[Message], meta: &Meta) -> Result<(), Error> {
+    let ext = path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    let report = Report::new(messages, meta);
+
+    match ext.as_str() {
+        "png" => draw_chart(BitMapBackend::new(path, (WIDTH, HEIGHT)).into_drawing_area(), &report)
+            .map_err(|e| Error::Draw(e.to_string())),
+        "svg" => draw_chart(SVGBackend::new(path, (WIDTH, HEIGHT)).into_drawing_area(), &report)
+            .map_err(|e| Error::Draw(e.to_string())),
+        "csv" => write_csv(BufWriter::new(File::create(path)?), &report),
+        "json" => {
+            let mut out = BufWriter::new(File::create(path)?);
+            serde_json::to_writer_pretty(&mut out, &report)?;
+            writeln!(out)?;
+            Ok(out.flush()?)
+        },
+        "ndjson" | "jsonl" => dump(BufWriter::new(File::create(path)?), messages),
+        _ => Err(Error::Format(ext)),
+    }
+}
+
+/// one long table of `series,day,hour,messages` rows, with the
+/// metadata up top as `#` comments
+pub fn write_csv<W: Write>(mut out: W, report: &Report) -> Result<(), Error> {
+    let meta = report.meta;
+    writeln!(out, "# user: {}", meta.user)?;
+    writeln!(out, "# source: {}", meta.source)?;
+    writeln!(out, "# zone: {}", meta.zone)?;
+    writeln!(out, "# range: {}", meta.range)?;
+    if let Some(t) = meta.fetched_at {
+        writeln!(out, "# fetched_at: {}", t.to_rfc3339())?;
+    }
+    writeln!(out, "# fetched: {} of {}", meta.fetched, meta.total_results)?;
+    if let Some(guess) = meta.zone_guess {
+        writeln!(out, "# zone_guess: {}", guess)?;
+    }
+
+    writeln!(out, "series,day,hour,messages")?;
+    for (hour, n) in report.hours.iter().enumerate() {
+        writeln!(out, "hour,,{},{}", hour, n)?;
+    }
+    for (day, hours) in ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"].iter().zip(report.weekday_hours.iter()) {
+        for (hour, n) in hours.iter().enumerate() {
+            writeln!(out, "weekday_hour,{},{},{}", day, hour, n)?;
+        }
+    }
+    for (day, n) in &report.daily {
+        writeln!(out, "daily,{},,{}", day, n)?;
+    }
+    Ok(out.flush()?)
+}
+
+/// every message as a line of json, for other tools or to `load` later
+pub fn dump<W: Write>(mut out: W, messages: &[Message]) -> Result<(), Error> {
+    for m in messages {
+        serde_json::to_writer(&mut out, m)?;
+        writeln!(out)?;
+    }
+    Ok(out.flush()?)
+}
+
+/// read back a dump written by `dump`, skipping blank lines
+pub fn load(path: &Path) -> Result<Vec<Message>, Error> {
+    let mut messages = Vec::new();
+    for (n, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        messages.push(serde_json::from_str(&line).map_err(|e| Error::Line(n + 1, e))?);
+    }
+    Ok(messages)
+}
+
+fn draw_chart<DB: DrawingBackend>(root: DrawingArea<DB, Shift>, report: &Report)
+    -> Result<(), DrawingAreaErrorKind<DB::ErrorType>>
+{
+    let (hours, meta) = (&report.hours, report.meta);
+    root.fill(&WHITE)?;
+    let root = root.margin(10, 10, 10, 10);
+    let root = root.titled(&format!("Activity of {}", meta.user), ("sans-serif", 28))?;
+    let root = root.titled(&format!("in {} · {}", meta.source, meta.range), ("sans-serif", 18).into_font().color(&RGBColor(0x60, 0x60, 0x60)))?;
+
+    let max = hours.iter().copied().max().unwrap_or(0);
+    let mut chart = ChartBuilder::on(&root)
+        .margin(10)
+        .x_label_area_size(50)
+        .y_label_area_size(60)
+        .build_cartesian_2d((0u32..23u32).into_segmented(), 0u32..max + max / 10 + 1)?;
+
+    chart.configure_mesh()
+        .disable_x_mesh()
+        .x_labels(24)
+        .x_label_formatter(&|v| match v {
+            SegmentValue::CenterOf(h) => HOUR_LABELS[*h as usize].to_string(),
+            _ => String::new(),
+        })
+        .label_style(("sans-serif", 15))
+        .axis_desc_style(("sans-serif", 17))
+        .x_desc(format!("Hour of day ({})", meta.zone))
+        .y_desc("Messages")
+        .draw()?;
+
+    chart.draw_series(
+        Histogram::vertical(&chart)
+            .style(BAR.filled())
+            .margin(6)
+            .data(hours.iter().enumerate().map(|(h, &n)| (h as u32, n))),
+    )?;
+
+    root.present()
+}
@@ -0,0 +1,239 @@
+use std::{fmt, io::{self, Write}, path::PathBuf};
+use clap::ValueEnum;
+
+use how_active::analysis::{self, HOUR_LABELS};
+use how_active::cache::{self, Cache, Fetch, Key};
+use how_active::discord::{self, Channel, Client, Guild, Message};
+use how_active::export::{self, Meta, Report, Source};
+use how_active::range::{DateRange, ParseRangeError};
+use how_active::zone::Zone;
+
+/// widest a bar in the table gets
+const BAR_WIDTH: u32 = 40;
+
+/// how the histogram gets printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Format {
+    #[default]
+    Table,
+    Csv,
+    Json,
+}
+
+/// what to look at
+pub struct Options {
+    pub user: Option<String>,
+    pub channel: Option<String>,
+    pub guild: Option<String>,
+    pub range: DateRange,
+    pub zone: Zone,
+    // read messages from a dump instead of fetching them
+    pub load: Option<PathBuf>,
+    // pick up from and keep what was fetched last time
+    pub cache: Option<Cache>,
+    // carry on with the last fetch that didn't finish instead
+    pub resume: bool,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    NoToken,
+    NoUser,
+    // neither a channel nor a guild to search in
+    NoSource,
+    // --target naming something the config doesn't have
+    UnknownTarget(String),
+    // nothing to --resume
+    NoCheckpoint,
+    // a range that ends up back to front once 90d and the like are counted back
+    Range(ParseRangeError),
+    Discord(discord::Error),
+    Export(export::Error),
+    Cache(cache::Error),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoToken => write!(f, "No token, pass --token-file or set DISCORD_TOKEN"),
+            Error::NoUser => write!(f, "Need a --user to look at"),
+            Error::NoSource => write!(f, "Need a guild --channel or a --guild to search in"),
+            Error::NoCheckpoint => write!(f, "No unfinished fetch to resume"),
+            Error::UnknownTarget(name) => write!(f, "No saved target called `{}` in the config", name),
+            Error::Range(e) => write!(f, "{}", e),
+            Error::Discord(e) => write!(f, "{}", e),
+            Error::Export(e) => write!(f, "{}", e),
+            Error::Cache(e) => write!(f, "{}", e),
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<discord::Error> for Error {
+    fn from(e: discord::Error) -> Self {
+        Error::Discord(e)
+    }
+}
+
+impl From<export::Error> for Error {
+    fn from(e: export::Error) -> Self {
+        Error::Export(e)
+    }
+}
+
+impl From<cache::Error> for Error {
+    fn from(e: cache::Error) -> Self {
+        Error::Cache(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// fetch messages without the tui, or read them from a dump when
+/// `load` is set. progress goes to stderr
+pub fn collect(client: Option<&Client>, opts: &Options) -> Result<(Vec<Message>, Meta), Error> {
+    if let Some(path) = &opts.load {
+        return Ok(loaded(export::load(path)?, opts));
+    }
+
+    let client = client.ok_or(Error::NoToken)?;
+    // carrying on from an interrupted fetch takes its target and range over
+    let resumed = match (&opts.cache, opts.resume) {
+        (Some(cache), true) => Some(cache.resume()?.ok_or(Error::NoCheckpoint)?),
+        (None, true) => return Err(Error::NoCheckpoint),
+        (_, false) => None,
+    };
+    let (user_id, channel_id, guild_id, range) = match &resumed {
+        Some((checkpoint, _)) => {
+            let position = &checkpoint.position;
+            (Some(position.user.clone()), position.channel.clone(), Some(position.guild.clone()), checkpoint.range)
+        },
+        None => (opts.user.clone(), opts.channel.clone(), opts.guild.clone(), opts.range),
+    };
+
+    let user = discord::get_user(user_id.as_deref().ok_or(Error::NoUser)?, client)?;
+    let (source, guild_id) = match (&channel_id, guild_id) {
+        (Some(id), _) => {
+            let channel = discord::get_channel(id, client)?;
+            let guild_id = channel.guild_id.clone();
+            (Source::Channel(channel), guild_id)
+        },
+        (None, Some(id)) => (Source::Guild(discord::get_guild(&id, client)?), id),
+        (None, None) => return Err(Error::NoSource),
+    };
+    // DMs don't have a guild to search
+    if guild_id.is_empty() {
+        return Err(Error::NoSource);
+    }
+
+    let key = Key {
+        guild: guild_id.clone(),
+        channel: channel_id.clone(),
+        author: user.id.clone(),
+    };
+    if let Some((_, messages)) = &resumed {
+        eprintln!("Resuming with {} messages already fetched", messages.len());
+    }
+    let resuming = resumed.is_some();
+    let (mut fetch, mut messenger) = Fetch::start(client, opts.cache.clone(), key, range, resumed)?;
+    let cached = fetch.cached();
+    if cached > 0 && !resuming {
+        eprintln!("{} messages cached, fetching newer ones", cached);
+    }
+
+    while let Some(page) = messenger.next() {
+        let page = page?;
+        if let Err(e) = fetch.page(messenger.position(), &page) {
+            eprintln!("Couldn't save checkpoint, carrying on without: {}", e);
+        }
+        match messenger.wait() {
+            Some(wait) => eprintln!("{}", wait),
+            None => eprintln!("Fetched {}/{}", messenger.fetched(), messenger.total_results()),
+        }
+    }
+    let messages = fetch.finish()?;
+
+    let meta = Meta {
+        user,
+        source,
+        zone: opts.zone,
+        range,
+        fetched_at: Some(client.now()),
+        total_results: messenger.total_results() + cached as u32,
+        fetched: messages.len(),
+        zone_guess: analysis::guess_zone(&analysis::utc_hours(&messages)),
+    };
+    Ok((messages, meta))
+}
+
+// make do with what a dump tells us about who and where
+fn loaded(messages: Vec<Message>, opts: &Options) -> (Vec<Message>, Meta) {
+    let messages = messages.into_iter()
+        .filter(|m| m.time().is_some_and(|t| opts.range.contains(t)))
+        .filter(|m| opts.user.as_ref().is_none_or(|id| &m.author.id == id))
+        .collect::<Vec<Message>>();
+
+    let first = messages.first();
+    let user = first.map(|m| m.author.clone()).unwrap_or_default();
+    let guild_id = first.and_then(|m| m.guild_id).map(|id| id.to_string()).unwrap_or_default();
+    let source = match first.map(|m| m.channel_id) {
+        Some(channel_id) if messages.iter().all(|m| m.channel_id == channel_id) => Source::Channel(Channel {
+            id: channel_id.to_string(),
+            guild_id,
+            ..Channel::default()
+        }),
+        _ => Source::Guild(Guild {
+            id: guild_id,
+            ..Guild::default()
+        }),
+    };
+
+    let meta = Meta {
+        user,
+        source,
+        zone: opts.zone,
+        range: opts.range,
+        fetched_at: None,
+        total_results: messages.len() as u32,
+        fetched: messages.len(),
+        zone_guess: analysis::guess_zone(&analysis::utc_hours(&messages)),
+    };
+    (messages, meta)
+}
+
+/// print the histogram to stdout
+pub fn report(messages: &[Message], meta: &Meta, format: Format) -> Result<(), Error> {
+    let report = Report::new(messages, meta);
+    let mut out = io::stdout().lock();
+    match format {
+        Format::Table => print_table(&mut out, &report)?,
+        Format::Csv => export::write_csv(&mut out, &report)?,
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, &report).map_err(export::Error::Json)?;
+            writeln!(out)?;
+        },
+    }
+    Ok(())
+}
+
+fn print_table<W: Write>(out: &mut W, report: &Report) -> io::Result<()> {
+    let meta = report.meta;
+    writeln!(out, "{} in {} · {} · {}", meta.user, meta.source, meta.range, meta.zone)?;
+    writeln!(out, "{} of {} messages", meta.fetched, meta.total_results)?;
+    if let Some(guess) = meta.zone_guess {
+        writeln!(out, "Likely in {}", guess)?;
+    }
+    writeln!(out)?;
+
+    let max = report.hours.iter().copied().max().unwrap_or(0).max(1);
+    for (label, n) in HOUR_LABELS.iter().zip(report.hours) {
+        let bar = (n * BAR_WIDTH).div_ceil(max) as usize;
+        writeln!(out, "{:>5} {:>7} {}", label, n, "█".repeat(bar))?;
+    }
+    Ok(())
+}
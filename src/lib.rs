@@ -0,0 +1,34 @@
+//! Fetch someone's Discord messages and work out when they're active.
+//!
+//! [`discord`] talks to the API, [`Messenger`](discord::Messenger) pages
+//! through search results for a user and waits out rate limits on its own.
+//! [`analysis`] turns the messages into histograms and statistics, and
+//! [`export`] writes them out as charts, CSV or JSON.
+//!
+//! ```no_run
+//! use how_active::{analysis::Tally, discord::{self, Client, Messenger, Token}, zone::Zone};
+//!
+//! # fn main() -> discord::Result<()> {
+//! let client = Client::new(Token::new("token").unwrap());
+//! let channel = discord::get_channel("41771983423143937", &client)?;
+//! let messenger = Messenger::new(client, "80351110224678912".into(), channel.guild_id, Some(channel.id));
+//!
+//! let mut tally = Tally::new(Zone::Local);
+//! for page in messenger {
+//!     page?.iter().for_each(|m| tally.add(m));
+//! }
+//! println!("{:?}", tally.stats(chrono::Utc::now().date_naive()));
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod analysis;
+pub mod cache;
+pub mod discord;
+pub mod export;
+pub mod range;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod tape;
+pub mod worker;
+pub mod zone;
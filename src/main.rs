@@ -1,43 +1,174 @@
-use std::{io, env, process};
+use std::{fs::File, io::{self, BufWriter}, path::PathBuf, process};
+use chrono::{DateTime, Utc};
+use clap::Parser;
 
-use reqwest::StatusCode;
+use how_active::{cache::Cache, discord::{Client, Token}, export, tape::Tape};
 
 mod app;
-mod discord;
+mod cli;
+mod config;
+mod headless;
+
+use cli::{Cli, Command, Target, TokenArgs, TuiArgs, Window};
+use config::Config;
 
 fn main() -> io::Result<()> {
-    let token = match env::args().skip(1).next() {
-        Some(val) => val,
-        None => match env::var("DISCORD_TOKEN") {
-            Ok(val) => val,
-            Err(_) => usage(1),
+    let cli = Cli::parse();
+    let tape = match (&cli.record, &cli.replay) {
+        (Some(path), _) => Some(Tape::record(path)),
+        (_, Some(path)) => Some(Tape::replay(path)),
+        _ => None,
+    };
+    let tape = match tape.transpose() {
+        Ok(tape) => tape,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
         },
     };
+    let config = match Config::load(cli.config.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        },
+    };
+    let command = cli.command.unwrap_or_else(|| Command::Tui(TuiArgs::default()));
+    // working off a dump doesn't need a token, don't go asking for one.
+    // neither does a replay, but the requests still want something to carry
+    let token = match &command {
+        Command::Report(cli::ReportArgs { load: Some(_), .. })
+        | Command::Export(cli::ExportArgs { load: Some(_), .. }) => None,
+        _ if cli.replay.is_some() => Token::new("replay"),
+        _ => token(&cli.token),
+    };
+    let client = token.map(|token| {
+        let client = Client::new(token).with_api_base(&cli.api_base);
+        match tape {
+            Some(tape) => client.with_tape(tape),
+            None => client,
+        }
+    });
+
+    // whatever's cached changes which requests get made, so recordings
+    // and replays go without
+    let cache = match cli.no_cache || cli.record.is_some() || cli.replay.is_some() {
+        true => None,
+        false => Cache::open().map(|mut cache| {
+            cache.refresh = cli.refresh;
+            cache
+        }),
+    };
+
+    // 90d and the like count back from when a replay was recorded
+    let now = client.as_ref().map_or_else(Utc::now, Client::now);
+
+    let res = match command {
+        Command::Tui(args) => return tui(client, config, cache, cli.resume, args),
+        Command::Fetch(args) => options(&config, cache, cli.resume, now, &args.target, &args.window, None)
+            .and_then(|opts| fetch(client.as_ref(), &opts, args.output)),
+        Command::Report(args) => options(&config, cache, cli.resume, now, &args.target, &args.window, args.load)
+            .and_then(|opts| headless::collect(client.as_ref(), &opts))
+            .and_then(|(messages, meta)| headless::report(&messages, &meta, args.format)),
+        Command::Export(args) => options(&config, cache, cli.resume, now, &args.target, &args.window, args.load)
+            .and_then(|opts| headless::collect(client.as_ref(), &opts))
+            .and_then(|(messages, meta)| Ok(export::write(&args.output, &messages, &meta)?)),
+    };
 
-    if token.is_empty() {
-        usage(1);
+    if let Err(e) = res {
+        eprintln!("Error: {}", e);
+        process::exit(1);
     }
+    Ok(())
+}
 
-    app::deploy(
-        &mut match app::App::new(token) {
-            Ok(a) => a,
+fn token(args: &TokenArgs) -> Option<Token> {
+    match args.resolve() {
+        Ok(token) => token,
+        Err(e) => {
+            eprintln!("Error: couldn't read token: {}", e);
+            process::exit(1);
+        },
+    }
+}
+
+fn fetch(client: Option<&Client>, opts: &headless::Options, output: Option<PathBuf>) -> Result<(), headless::Error> {
+    let (messages, _) = headless::collect(client, opts)?;
+    match &output {
+        Some(path) => export::dump(BufWriter::new(File::create(path)?), &messages)?,
+        None => export::dump(io::stdout().lock(), &messages)?,
+    }
+    Ok(())
+}
+
+fn options(config: &Config, cache: Option<Cache>, resume: bool, now: DateTime<Utc>, target: &Target, window: &Window, load: Option<PathBuf>)
+    -> Result<headless::Options, headless::Error>
+{
+    let target = target.resolve(config).map_err(headless::Error::UnknownTarget)?;
+    Ok(headless::Options {
+        user: target.user,
+        channel: target.channel,
+        guild: target.guild,
+        range: window.date_range(now).map_err(headless::Error::Range)?,
+        zone: window.zone(config),
+        load,
+        cache,
+        resume,
+    })
+}
+
+fn tui(client: Option<Client>, config: Config, cache: Option<Cache>, resume: bool, args: TuiArgs) -> io::Result<()> {
+    let client = match client {
+        Some(c) => c,
+        None => {
+            eprintln!("Error: {}", headless::Error::NoToken);
+            process::exit(1);
+        },
+    };
+
+    let mut app = match app::App::new(client) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        },
+    };
+    let target = match args.target.resolve(&config) {
+        Ok(t) => t,
+        Err(name) => {
+            eprintln!("Error: {}", headless::Error::UnknownTarget(name));
+            process::exit(1);
+        },
+    };
+    app.range = match args.window.date_range(app.client.now()) {
+        Ok(range) => range,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        },
+    };
+    app.set_zone(args.window.zone(&config));
+    app.export = args.export;
+    app.theme = config.theme;
+    app.export_dir = config.export_dir();
+    app.targets = config.targets;
+    app.cache = cache;
+    app.resume = resume;
+
+    let location = target.channel.as_deref().or(target.guild.as_deref());
+    if let Err(e) = app.preset(target.user.as_deref(), location) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+    if let Some(path) = args.load {
+        match export::load(&path) {
+            Ok(messages) => app.load(messages),
             Err(e) => {
-                match e.status() {
-                    Some(StatusCode::UNAUTHORIZED) => eprintln!("Invalid auth token"),
-                    _ => eprintln!("Error: {}", e),
-                }
+                eprintln!("{}: {}", path.display(), e);
                 process::exit(1);
             },
         }
-    )
-}
-
-fn usage(exit_code: i32) -> ! {
-    eprintln!("
-    Usage: how-active [token]
+    }
 
-    token => Your Discord authorization token (can be grabbed from request headers of Discord API requests)
-        Either add environment variable `DISCORD_TOKEN` with your token, or provide it as an argument.
-");
-    process::exit(exit_code);
+    app::deploy(&mut app)
 }
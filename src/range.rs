@@ -0,0 +1,222 @@
+use std::{error, fmt, str::FromStr};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+/// window of time to analyse, either end may be left open
+///
+/// parsed from specs like `90d`, `12w`, `2023-05`, `2023-05-14`,
+/// `2023-01-01..2023-03-31`, `2023-01-01..` or `..2023-03-31`.
+/// calendar dates are whole UTC days, so the end date is inclusive
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+pub struct ParseRangeError(String);
+
+impl fmt::Display for ParseRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid date range `{}` (try 90d, 2023-05 or 2023-01-01..2023-03-31)", self.0)
+    }
+}
+
+impl error::Error for ParseRangeError {}
+
+impl DateRange {
+    /// whether `t` falls inside the range
+    pub fn contains(&self, t: DateTime<Utc>) -> bool {
+        self.from.is_none_or(|from| t >= from) && self.to.is_none_or(|to| t < to)
+    }
+
+    /// parse a single bound, the start of whatever `s` names
+    pub fn parse_from(s: &str) -> Result<DateTime<Utc>, ParseRangeError> {
+        DateRange::parse_from_at(s, Utc::now())
+    }
+
+    /// parse a single bound, the end of whatever `s` names
+    pub fn parse_to(s: &str) -> Result<DateTime<Utc>, ParseRangeError> {
+        DateRange::parse_to_at(s, Utc::now())
+    }
+
+    /// `parse_from`, counting `90d` and the like back from `now`
+    pub fn parse_from_at(s: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, ParseRangeError> {
+        match Bound::parse(s)? {
+            Bound::Ago(d) => Ok(now - d),
+            Bound::Month(first) | Bound::Day(first) => Ok(midnight(first)),
+        }
+    }
+
+    /// `parse_to`, counting `90d` and the like back from `now`
+    pub fn parse_to_at(s: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, ParseRangeError> {
+        match Bound::parse(s)? {
+            Bound::Ago(d) => Ok(now - d),
+            Bound::Month(first) => Ok(midnight(next_month(first))),
+            Bound::Day(day) => Ok(midnight(day + Duration::days(1))),
+        }
+    }
+
+    /// parse a whole range spec, counting `90d` and the like back from `now`
+    /// rather than the current time
+    pub fn parse_at(s: &str, now: DateTime<Utc>) -> Result<Self, ParseRangeError> {
+        let s = s.trim();
+        if s.is_empty() || s == "all" || s == ".." {
+            return Ok(DateRange::default());
+        }
+
+        type Parse = fn(&str, DateTime<Utc>) -> Result<DateTime<Utc>, ParseRangeError>;
+        let bound = |s: &str, parse: Parse| {
+            match s.trim() {
+                "" => Ok(None),
+                s => parse(s, now).map(Some),
+            }
+        };
+        let range = match s.split_once("..") {
+            Some((from, to)) => DateRange {
+                from: bound(from, DateRange::parse_from_at)?,
+                to: bound(to, DateRange::parse_to_at)?,
+            },
+            // "90d" runs up to now, a date or month covers just itself
+            None => match Bound::parse(s)? {
+                Bound::Ago(_) => DateRange {
+                    from: Some(DateRange::parse_from_at(s, now)?),
+                    to: None,
+                },
+                _ => DateRange {
+                    from: Some(DateRange::parse_from_at(s, now)?),
+                    to: Some(DateRange::parse_to_at(s, now)?),
+                },
+            },
+        };
+
+        match range {
+            DateRange { from: Some(from), to: Some(to) } if from >= to =>
+                Err(ParseRangeError(s.to_string())),
+            _ => Ok(range),
+        }
+    }
+}
+
+impl FromStr for DateRange {
+    type Err = ParseRangeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DateRange::parse_at(s, Utc::now())
+    }
+}
+
+impl fmt::Display for DateRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.from, self.to) {
+            (None, None) => write!(f, "all time"),
+            (Some(from), None) => write!(f, "since {}", from.format("%Y-%m-%d")),
+            // `to` is exclusive, show the last day it still covers
+            (None, Some(to)) => write!(f, "until {}", last_day(to).format("%Y-%m-%d")),
+            (Some(from), Some(to)) => write!(f, "{} – {}",
+                from.format("%Y-%m-%d"), last_day(to).format("%Y-%m-%d")),
+        }
+    }
+}
+
+// one end of a range spec
+enum Bound {
+    // relative to now
+    Ago(Duration),
+    // first day of a month
+    Month(NaiveDate),
+    Day(NaiveDate),
+}
+
+impl Bound {
+    fn parse(s: &str) -> Result<Self, ParseRangeError> {
+        let err = || ParseRangeError(s.to_string());
+        let s = s.trim();
+
+        if let Some(unit) = s.chars().last().filter(|c| c.is_ascii_alphabetic()) {
+            // keep it to a century so the date math can't overflow
+            let n = s[..s.len() - 1].parse::<i64>().ok()
+                .filter(|n| (0..=36_500).contains(n))
+                .ok_or_else(err)?;
+            return match unit {
+                'd' => Ok(Bound::Ago(Duration::days(n))),
+                'w' => Ok(Bound::Ago(Duration::weeks(n))),
+                _ => Err(err()),
+            };
+        }
+
+        if let Ok(day) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(Bound::Day(day));
+        }
+        NaiveDate::parse_from_str(&format!("{}-01", s), "%Y-%m-%d")
+            .map(Bound::Month)
+            .map_err(|_| err())
+    }
+}
+
+fn midnight(day: NaiveDate) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&day.and_hms_opt(0, 0, 0).unwrap())
+}
+
+fn next_month(first: NaiveDate) -> NaiveDate {
+    match first.month() {
+        12 => NaiveDate::from_ymd_opt(first.year() + 1, 1, 1),
+        m => NaiveDate::from_ymd_opt(first.year(), m + 1, 1),
+    }
+    .unwrap()
+}
+
+fn last_day(to: DateTime<Utc>) -> DateTime<Utc> {
+    to - Duration::nanoseconds(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap()
+    }
+
+    fn parse(s: &str) -> Result<DateRange, ParseRangeError> {
+        DateRange::parse_at(s, at(2023, 5, 20))
+    }
+
+    #[test]
+    fn relative_ranges_count_back_from_now() {
+        assert_eq!(parse("90d").unwrap(), DateRange { from: Some(at(2023, 2, 19)), to: None });
+        assert_eq!(parse("12w").unwrap(), DateRange { from: Some(at(2023, 2, 25)), to: None });
+    }
+
+    #[test]
+    fn dates_cover_the_whole_day_or_month() {
+        assert_eq!(parse("2023-05").unwrap(), DateRange { from: Some(at(2023, 5, 1)), to: Some(at(2023, 6, 1)) });
+        assert_eq!(parse("2023-12").unwrap(), DateRange { from: Some(at(2023, 12, 1)), to: Some(at(2024, 1, 1)) });
+        assert_eq!(parse("2023-05-14").unwrap(), DateRange { from: Some(at(2023, 5, 14)), to: Some(at(2023, 5, 15)) });
+    }
+
+    #[test]
+    fn either_end_may_be_open() {
+        assert_eq!(parse("2023-01-01..2023-03-31").unwrap(), DateRange { from: Some(at(2023, 1, 1)), to: Some(at(2023, 4, 1)) });
+        assert_eq!(parse("..2023-03").unwrap(), DateRange { from: None, to: Some(at(2023, 4, 1)) });
+        assert_eq!(parse("2023-01-01..").unwrap(), DateRange { from: Some(at(2023, 1, 1)), to: None });
+        assert_eq!(parse("30d..7d").unwrap(), DateRange { from: Some(at(2023, 4, 20)), to: Some(at(2023, 5, 13)) });
+        for s in ["", "..", "all"] {
+            assert_eq!(parse(s).unwrap(), DateRange::default());
+        }
+    }
+
+    #[test]
+    fn rejects_reversed_ranges() {
+        for s in ["2023-05-10..2023-05-01", "2023-05..2023-04", "7d..30d", "2023-05-01..2023-04-30"] {
+            assert!(parse(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        for s in ["yesterday", "90x", "-5d", "d", "2023-13", "2023-02-30", "2023-05..soon", "99999999d"] {
+            assert!(parse(s).is_err(), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! An async [`Messenger`], for searching several channels or guilds at once.
+//!
+//! ```no_run
+//! use futures_util::StreamExt;
+//! use how_active::discord::{Client, Messenger};
+//! use how_active::stream::{AsyncMessenger, Limiter};
+//!
+//! # async fn run(client: Client) {
+//! // one limiter between them, so a rate limit on one holds them all off
+//! let limiter = Limiter::new();
+//! let tasks = ["41771983423143937", "41771983423143938"].map(|channel| {
+//!     let messenger = Messenger::new(client.clone(), "80351110224678912".into(),
+//!         "41771983423143936".into(), Some(channel.into()));
+//!     let mut pages = AsyncMessenger::new(messenger).with_limiter(limiter.clone());
+//!     tokio::spawn(async move {
+//!         let mut messages = Vec::new();
+//!         while let Some(page) = pages.next().await {
+//!             messages.extend(page?);
+//!         }
+//!         Ok::<_, how_active::discord::Error>(messages)
+//!     })
+//! });
+//! # }
+//! ```
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+    time::{Duration, Instant},
+};
+use futures_core::Stream;
+
+use crate::discord::{Message, Messenger, Result, Wait};
+
+/// when the next request may go out, shared between messengers so one
+/// getting rate limited holds the others off too
+#[derive(Debug, Clone, Default)]
+pub struct Limiter(Arc<Mutex<Option<Instant>>>);
+
+impl Limiter {
+    pub fn new() -> Self {
+        Limiter::default()
+    }
+
+    /// time left until requests may go out again
+    pub fn remaining(&self) -> Option<Duration> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+            .map(|t| t.saturating_duration_since(Instant::now()))
+    }
+
+    // hold everyone off for as long as a rate limit asks, indexing and
+    // server errors are the messenger's own problem
+    fn note(&self, messenger: &Messenger) {
+        if let (Some(Wait::RateLimit(_)), Some(left)) = (messenger.wait(), messenger.remaining()) {
+            let mut until = self.0.lock().unwrap_or_else(|e| e.into_inner());
+            *until = (*until).max(Some(Instant::now() + left));
+        }
+    }
+
+    // sit out both the messenger's own wait and everyone's
+    async fn wait(&self, messenger: &Messenger) {
+        while let Some(left) = messenger.remaining().max(self.remaining()).filter(|d| !d.is_zero()) {
+            tokio::time::sleep(left).await;
+        }
+    }
+}
+
+type Pending = Pin<Box<dyn Future<Output = (Messenger, Option<Result<Vec<Message>>>)> + Send>>;
+
+/// a [`Messenger`] that doesn't block, as a `Stream` of pages. like the
+/// blocking one it yields an empty page whenever it has to hold off, and
+/// stops after the first error
+pub struct AsyncMessenger {
+    // taken by the page that's being fetched
+    messenger: Option<Messenger>,
+    pending: Option<Pending>,
+    http: reqwest::Client,
+    limiter: Limiter,
+    // copied off the messenger after every page
+    fetched: usize,
+    total_results: u32,
+    wait: Option<Wait>,
+}
+
+impl AsyncMessenger {
+    /// page through what `messenger` would, its range and position included
+    pub fn new(messenger: Messenger) -> Self {
+        AsyncMessenger {
+            fetched: messenger.fetched(),
+            total_results: messenger.total_results(),
+            wait: messenger.wait(),
+            messenger: Some(messenger),
+            pending: None,
+            http: reqwest::Client::new(),
+            limiter: Limiter::new(),
+        }
+    }
+
+    /// share rate limits with every other messenger using `limiter`
+    pub fn with_limiter(mut self, limiter: Limiter) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    /// make requests with `http`, to share its connections
+    pub fn with_http(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// unique messages yielded so far
+    pub fn fetched(&self) -> usize {
+        self.fetched
+    }
+
+    /// how many messages the search says there are
+    pub fn total_results(&self) -> u32 {
+        self.total_results
+    }
+
+    /// why the stream's holding off on its next page, if it is
+    pub fn wait(&self) -> Option<Wait> {
+        self.wait
+    }
+
+    /// the messenger underneath, `None` while a page is being fetched
+    pub fn messenger(&self) -> Option<&Messenger> {
+        self.messenger.as_ref()
+    }
+}
+
+impl Stream for AsyncMessenger {
+    type Item = Result<Vec<Message>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if this.pending.is_none() {
+            match this.messenger.take() {
+                Some(messenger) if messenger.ready() => {
+                    this.pending = Some(next_page(messenger, this.http.clone(), this.limiter.clone()));
+                },
+                done => {
+                    this.messenger = done;
+                    return Poll::Ready(None);
+                },
+            }
+        }
+
+        let pending = this.pending.as_mut().expect("a page is being fetched");
+        let (messenger, page) = ready!(pending.as_mut().poll(cx));
+        this.pending = None;
+        this.fetched = messenger.fetched();
+        this.total_results = messenger.total_results();
+        this.wait = messenger.wait();
+        this.messenger = Some(messenger);
+        Poll::Ready(page)
+    }
+}
+
+// fetch a page with the messenger moved in, handing it back along with the page
+fn next_page(mut messenger: Messenger, http: reqwest::Client, limiter: Limiter) -> Pending {
+    Box::pin(async move {
+        limiter.wait(&messenger).await;
+        let fetched = messenger.fetch_async(&http).await;
+        let page = messenger.settle(fetched);
+        limiter.note(&messenger);
+        (messenger, page)
+    })
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// one request and the response it got, a line of a recording
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Exchange {
+    // relative to the api base, query included
+    pub path: String,
+    // when the response came in
+    pub at: DateTime<Utc>,
+    pub status: u16,
+    // only the rate limit ones, nothing else in there is of use to us
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+/// first line of a recording, when it was started
+#[derive(Serialize, Deserialize, Debug)]
+struct Header {
+    started: DateTime<Utc>,
+}
+
+/// every request the discord module makes, either written down as it
+/// goes or answered from an earlier recording without touching the network
+pub struct Tape {
+    mode: Mode,
+    // when the latest response came in, or was recorded when replaying.
+    // when the recording started until then
+    last: Mutex<Option<DateTime<Utc>>>,
+}
+
+enum Mode {
+    Record(PathBuf, Mutex<BufWriter<File>>),
+    // what hasn't been played back yet, in the order it was recorded
+    Replay(Mutex<Vec<Exchange>>),
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Json(PathBuf, serde_json::Error),
+    // replaying, and the recording never made this request
+    Missing(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "Recording {}: {}", path.display(), e),
+            Error::Json(path, e) => write!(f, "Recording {}: {}", path.display(), e),
+            Error::Missing(path) => write!(f, "Nothing recorded for {}", path),
+        }
+    }
+}
+
+impl Exchange {
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_str(&self.body)
+    }
+
+    /// whether a response header is worth keeping
+    pub fn keeps(name: &str) -> bool {
+        name.starts_with("x-ratelimit-") || name == "retry-after"
+    }
+}
+
+impl Tape {
+    fn new(mode: Mode, started: Option<DateTime<Utc>>) -> Self {
+        Tape {
+            mode,
+            last: Mutex::new(started),
+        }
+    }
+
+    /// start recording to `path`, replacing whatever's there
+    pub fn record(path: &Path) -> Result<Self, Error> {
+        let file = File::create(path).map_err(|e| Error::Io(path.to_path_buf(), e))?;
+        let mut out = BufWriter::new(file);
+        let header = Header { started: Utc::now() };
+        serde_json::to_writer(&mut out, &header).map_err(|e| Error::Json(path.to_path_buf(), e))?;
+        writeln!(out).map_err(|e| Error::Io(path.to_path_buf(), e))?;
+        Ok(Tape::new(Mode::Record(path.to_path_buf(), Mutex::new(out)), Some(header.started)))
+    }
+
+    /// play back the recording at `path`
+    pub fn replay(path: &Path) -> Result<Self, Error> {
+        let file = File::open(path).map_err(|e| Error::Io(path.to_path_buf(), e))?;
+        let mut started = None;
+        let mut exchanges = Vec::<Exchange>::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| Error::Io(path.to_path_buf(), e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Header>(&line) {
+                Ok(header) => started = Some(header.started),
+                Err(_) => exchanges.push(serde_json::from_str(&line).map_err(|e| Error::Json(path.to_path_buf(), e))?),
+            }
+        }
+        // without a header, the first response is the closest we've got
+        let started = started.or_else(|| exchanges.first().map(|e| e.at));
+        Ok(Tape::new(Mode::Replay(Mutex::new(exchanges)), started))
+    }
+
+    pub fn replaying(&self) -> bool {
+        matches!(self.mode, Mode::Replay(_))
+    }
+
+    /// when the latest response came in, the same on replay as it was
+    /// when recording. when the recording started before the first one
+    pub fn last(&self) -> Option<DateTime<Utc>> {
+        *self.last.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn seen(&self, exchange: &Exchange) {
+        *self.last.lock().unwrap_or_else(|e| e.into_inner()) = Some(exchange.at);
+    }
+
+    /// note an exchange down, a line at a time so a crash loses nothing
+    /// before it. does nothing when replaying
+    pub fn write(&self, exchange: &Exchange) -> Result<(), Error> {
+        let (path, out) = match &self.mode {
+            Mode::Record(path, out) => (path, out),
+            Mode::Replay(_) => return Ok(()),
+        };
+        let mut out = out.lock().unwrap_or_else(|e| e.into_inner());
+        serde_json::to_writer(&mut *out, exchange).map_err(|e| Error::Json(path.clone(), e))?;
+        writeln!(out).and_then(|_| out.flush()).map_err(|e| Error::Io(path.clone(), e))?;
+        self.seen(exchange);
+        Ok(())
+    }
+
+    /// the recorded response to a request for `path`, the earliest one left
+    /// that asked for exactly the same thing
+    pub fn play(&self, path: &str) -> Result<Exchange, Error> {
+        let left = match &self.mode {
+            Mode::Replay(left) => left,
+            Mode::Record(..) => return Err(Error::Missing(path.to_string())),
+        };
+        let mut left = left.lock().unwrap_or_else(|e| e.into_inner());
+        let exchange = left.iter().position(|e| e.path == path)
+            .map(|i| left.remove(i))
+            .ok_or_else(|| Error::Missing(path.to_string()))?;
+        self.seen(&exchange);
+        Ok(exchange)
+    }
+}
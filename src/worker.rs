@@ -0,0 +1,94 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::discord::{self, Message, Messenger, Position, Wait};
+
+/// longest the worker sleeps through a wait before checking for a cancel
+const STEP: Duration = Duration::from_millis(100);
+
+/// what the fetch thread sends back
+pub enum Update {
+    Page {
+        messages: Vec<Message>,
+        // where the messenger is up to after this page, to checkpoint
+        position: Position,
+        fetched: usize,
+        total_results: u32,
+        wait: Option<Wait>,
+    },
+    Failed(discord::Error),
+    Done,
+    // the thread went away without a word, it must have panicked
+    Crashed,
+}
+
+/// a messenger running on its own thread, so whoever's waiting on it
+/// doesn't have to sit through its requests and rate limits
+pub struct Worker {
+    updates: Receiver<Update>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Worker {
+    pub fn spawn(mut messenger: Messenger) -> Self {
+        let (tx, updates) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let cancel = cancelled.clone();
+
+        thread::spawn(move || loop {
+            // sit out waits here in small steps so a cancel gets noticed
+            while let Some(left) = messenger.remaining().filter(|d| !d.is_zero()) {
+                if cancel.load(Ordering::Relaxed) {
+                    return;
+                }
+                thread::sleep(left.min(STEP));
+            }
+            if cancel.load(Ordering::Relaxed) {
+                return;
+            }
+
+            let update = match messenger.next() {
+                Some(Ok(messages)) => Update::Page {
+                    messages,
+                    position: messenger.position(),
+                    fetched: messenger.fetched(),
+                    total_results: messenger.total_results(),
+                    wait: messenger.wait(),
+                },
+                Some(Err(e)) => Update::Failed(e),
+                None => Update::Done,
+            };
+            let last = !matches!(update, Update::Page { .. });
+            // nobody's listening anymore, or there's nothing left to send
+            if tx.send(update).is_err() || last {
+                return;
+            }
+        });
+
+        Worker { updates, cancelled }
+    }
+
+    /// the next update if there's one waiting, `None` otherwise
+    pub fn poll(&self) -> Option<Update> {
+        match self.updates.try_recv() {
+            Ok(update) => Some(update),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Update::Crashed),
+        }
+    }
+}
+
+/// dropping a worker cancels it, it stops after whatever request is in
+/// flight without anyone waiting on it
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,153 @@
+use std::{error, fmt, str::FromStr};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, Offset, Utc};
+use chrono_tz::Tz;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// timezone to bucket activity in
+///
+/// parsed from `local`, `utc`, a fixed offset like `+02:00`, `-5` or
+/// `UTC+5:30`, or an IANA name like `Europe/Prague`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Zone {
+    // whatever the machine we're running on is set to
+    #[default]
+    Local,
+    Fixed(FixedOffset),
+    // named zones follow their DST rules
+    Named(Tz),
+}
+
+#[derive(Debug)]
+pub struct ParseZoneError(String);
+
+impl fmt::Display for ParseZoneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unknown timezone `{}` (try local, utc, +02:00 or Europe/Prague)", self.0)
+    }
+}
+
+impl error::Error for ParseZoneError {}
+
+impl Zone {
+    /// wall-clock time of `t` in this zone
+    pub fn localize(&self, t: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Zone::Local => t.with_timezone(&Local).naive_local(),
+            Zone::Fixed(offset) => t.with_timezone(offset).naive_local(),
+            Zone::Named(tz) => t.with_timezone(tz).naive_local(),
+        }
+    }
+
+    /// how far ahead of UTC the zone is at `t`
+    pub fn offset(&self, t: DateTime<Utc>) -> FixedOffset {
+        match self {
+            Zone::Local => t.with_timezone(&Local).offset().fix(),
+            Zone::Fixed(offset) => *offset,
+            Zone::Named(tz) => t.with_timezone(tz).offset().fix(),
+        }
+    }
+}
+
+impl FromStr for Zone {
+    type Err = ParseZoneError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let err = || ParseZoneError(s.to_string());
+
+        match s.to_ascii_lowercase().as_str() {
+            "local" => return Ok(Zone::Local),
+            "utc" | "gmt" | "z" => return Ok(Zone::Named(Tz::UTC)),
+            _ => {},
+        }
+
+        // "UTC+2" and "GMT-5:30" are just fixed offsets with a prefix
+        let offset = ["UTC", "utc", "GMT", "gmt"].iter()
+            .find_map(|p| s.strip_prefix(p))
+            .unwrap_or(s);
+        if offset.starts_with(['+', '-']) {
+            return parse_offset(offset).map(Zone::Fixed).ok_or_else(err);
+        }
+
+        s.parse::<Tz>().map(Zone::Named).map_err(|_| err())
+    }
+}
+
+/// written the way it's parsed, so `Local` stays `local` rather than
+/// whatever offset the machine writing it had
+impl Serialize for Zone {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Zone::Local => serializer.serialize_str("local"),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Zone {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Zone::Local => write!(f, "Local (UTC{})", self.offset(Utc::now())),
+            Zone::Fixed(offset) => write!(f, "UTC{}", offset),
+            Zone::Named(tz) => write!(f, "{}", tz.name()),
+        }
+    }
+}
+
+// "+2", "-05", "+0530", "+05:30"
+fn parse_offset(s: &str) -> Option<FixedOffset> {
+    let (sign, rest) = match s.split_at(1) {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+    // digits only, so a stray sign doesn't parse and the split below
+    // can't land inside a multibyte character
+    if !rest.chars().all(|c| c.is_ascii_digit() || c == ':') {
+        return None;
+    }
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if rest.len() > 2 => rest.split_at(rest.len() - 2),
+        None => (rest, "0"),
+    };
+    let hours = hours.parse::<i32>().ok().filter(|h| *h <= 14)?;
+    let minutes = minutes.parse::<i32>().ok().filter(|m| *m < 60)?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_serde() {
+        for s in ["local", "utc", "+05:30", "UTC-3", "Europe/Prague"] {
+            let zone = s.parse::<Zone>().unwrap();
+            let json = serde_json::to_string(&zone).unwrap();
+            assert_eq!(serde_json::from_str::<Zone>(&json).unwrap(), zone, "{}", json);
+        }
+        assert_eq!(serde_json::to_string(&Zone::Local).unwrap(), "\"local\"");
+    }
+
+    #[test]
+    fn parses_offsets() {
+        let east = |secs| Some(Zone::Fixed(FixedOffset::east_opt(secs).unwrap()));
+        assert_eq!("+2".parse::<Zone>().ok(), east(2 * 3600));
+        assert_eq!("-0530".parse::<Zone>().ok(), east(-(5 * 3600 + 30 * 60)));
+        assert_eq!("UTC+05:30".parse::<Zone>().ok(), east(5 * 3600 + 30 * 60));
+    }
+
+    #[test]
+    fn rejects_bad_offsets() {
+        for s in ["+é1", "+", "+-5", "+15", "+2:60", "+1é"] {
+            assert!(s.parse::<Zone>().is_err(), "{}", s);
+        }
+    }
+}
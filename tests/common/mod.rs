@@ -0,0 +1,204 @@
+//! a stand-in for the bits of the discord api how-active talks to
+
+// every test binary pulls this in and uses a different part of it
+#![allow(dead_code)]
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io::Cursor,
+    path::Path,
+    process::Command,
+    sync::{Arc, Mutex},
+    thread,
+};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde_json::{json, Value};
+use tiny_http::{Header, Request, Response, Server};
+
+pub const TOKEN: &str = "mock-token";
+pub const USER: &str = "80351110224678912";
+pub const CHANNEL: &str = "41771983423143937";
+pub const GUILD: &str = "41771983423143936";
+
+const DISCORD_EPOCH: i64 = 1_420_070_400_000;
+const PAGE_SIZE: usize = 25;
+
+/// how to answer a search request instead of serving the next page
+pub enum Reply {
+    Page,
+    RateLimited(f64),
+    Indexing(f64),
+    Status(u16),
+}
+
+type Response_ = Response<Cursor<Vec<u8>>>;
+
+struct State {
+    // newest first
+    messages: Vec<Value>,
+    script: VecDeque<Reply>,
+    searches: Vec<String>,
+}
+
+pub struct Mock {
+    pub url: String,
+    state: Arc<Mutex<State>>,
+    server: Arc<Server>,
+}
+
+impl Mock {
+    /// serve `messages` (newest first) from a fresh server on a free port
+    pub fn start(messages: Vec<Value>) -> Self {
+        let server = Arc::new(Server::http("127.0.0.1:0").expect("mock server"));
+        let url = format!("http://{}", server.server_addr().to_ip().expect("tcp address"));
+        let state = Arc::new(Mutex::new(State {
+            messages,
+            script: VecDeque::new(),
+            searches: Vec::new(),
+        }));
+
+        let (srv, st) = (server.clone(), state.clone());
+        thread::spawn(move || {
+            for request in srv.incoming_requests() {
+                let response = handle(&request, &mut st.lock().unwrap());
+                let _ = request.respond(response);
+            }
+        });
+
+        Mock { url, state, server }
+    }
+
+    /// answer the next search requests like this, one reply each
+    pub fn script(&self, replies: impl IntoIterator<Item = Reply>) {
+        self.state.lock().unwrap().script.extend(replies);
+    }
+
+    /// query strings of every search request so far
+    pub fn searches(&self) -> Vec<HashMap<String, String>> {
+        self.state.lock().unwrap().searches.iter().map(|q| query(q)).collect()
+    }
+
+    /// the binary pointed at us, with its cache and config kept in `home`
+    pub fn command(&self, home: &Path) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_how-active"));
+        cmd.env("HOW_ACTIVE_API_BASE", &self.url)
+            .env("XDG_CACHE_HOME", home.join("cache"))
+            .env("XDG_CONFIG_HOME", home.join("config"))
+            .env("HOME", home)
+            .env_remove("DISCORD_TOKEN");
+        cmd
+    }
+
+    /// `command` with the token the mock takes in the environment
+    pub fn authed(&self, home: &Path) -> Command {
+        let mut cmd = self.command(home);
+        cmd.env("DISCORD_TOKEN", TOKEN);
+        cmd
+    }
+}
+
+impl Drop for Mock {
+    fn drop(&mut self) {
+        self.server.unblock();
+    }
+}
+
+/// `n` messages from `USER` in `CHANNEL`, three hours apart and newest
+/// first, the newest sent at `newest`
+pub fn messages(n: usize, newest: DateTime<Utc>) -> Vec<Value> {
+    (0..n).map(|i| message(newest - Duration::hours(3 * i as i64), i)).collect()
+}
+
+/// the first moment of a day in may 2023
+pub fn may(day: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2023, 5, day, 0, 0, 0).unwrap()
+}
+
+fn message(at: DateTime<Utc>, n: usize) -> Value {
+    let id = ((at.timestamp_millis() - DISCORD_EPOCH) as u64) << 22;
+    json!({
+        "id": id.to_string(),
+        "type": 0,
+        "author": fixture("user"),
+        "timestamp": at.to_rfc3339(),
+        "edited_timestamp": null,
+        "content": format!("message {}", n),
+        "channel_id": CHANNEL,
+    })
+}
+
+fn fixture(name: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(format!("{}.json", name));
+    serde_json::from_str(&fs::read_to_string(path).expect("fixture")).expect("fixture json")
+}
+
+fn query(q: &str) -> HashMap<String, String> {
+    q.split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn json_response(status: u16, body: &Value) -> Response_ {
+    Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+}
+
+fn handle(request: &Request, state: &mut State) -> Response_ {
+    let authorized = request.headers().iter()
+        .any(|h| h.field.equiv("Authorization") && h.value.as_str() == TOKEN);
+    if !authorized {
+        return json_response(401, &json!({ "message": "401: Unauthorized", "code": 0 }));
+    }
+
+    let (path, q) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    let parts = path.trim_start_matches('/').split('/').collect::<Vec<&str>>();
+    let not_found = || json_response(404, &json!({ "message": "Unknown", "code": 10000 }));
+    match parts.as_slice() {
+        ["users", id] if *id == USER || *id == "@me" => json_response(200, &fixture("user")),
+        ["channels", id] if *id == CHANNEL => json_response(200, &fixture("channel")),
+        ["guilds", id] if *id == GUILD => json_response(200, &fixture("guild")),
+        ["guilds", id, "messages", "search"] if *id == GUILD => {
+            state.searches.push(q.to_string());
+            match state.script.pop_front().unwrap_or(Reply::Page) {
+                Reply::Page => json_response(200, &search(&state.messages, &query(q))),
+                Reply::RateLimited(secs) => json_response(429, &json!({
+                    "message": "You are being rate limited.",
+                    "retry_after": secs,
+                    "global": false,
+                })),
+                Reply::Indexing(secs) => json_response(202, &json!({
+                    "message": "Index not yet available. Try again later",
+                    "code": 110000,
+                    "documents_indexed": 0,
+                    "retry_after": secs,
+                })),
+                Reply::Status(code) => json_response(code, &json!({ "message": "scripted", "code": 0 })),
+            }
+        },
+        _ => not_found(),
+    }
+}
+
+// a page of search results the way discord pages them: filtered by the
+// query, newest first, each hit wrapped in its own context array
+fn search(messages: &[Value], q: &HashMap<String, String>) -> Value {
+    let id = |m: &Value| m["id"].as_str().unwrap().parse::<u64>().unwrap();
+    let bound = |k: &str| q.get(k).map(|v| v.parse::<u64>().unwrap());
+    let (min_id, max_id) = (bound("min_id"), bound("max_id"));
+    let offset = q.get("offset").map_or(0, |v| v.parse::<usize>().unwrap());
+
+    let hits = messages.iter()
+        .filter(|m| q.get("author_id").is_none_or(|a| m["author"]["id"] == a.as_str()))
+        .filter(|m| q.get("channel_id").is_none_or(|c| m["channel_id"] == c.as_str()))
+        .filter(|m| min_id.is_none_or(|min| id(m) > min))
+        .filter(|m| max_id.is_none_or(|max| id(m) < max))
+        .collect::<Vec<&Value>>();
+
+    json!({
+        "total_results": hits.len(),
+        "messages": hits.iter().skip(offset).take(PAGE_SIZE).map(|m| vec![*m]).collect::<Vec<_>>(),
+    })
+}
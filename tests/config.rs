@@ -0,0 +1,80 @@
+//! saved targets and settings from the config file
+
+mod common;
+
+use std::{fs, path::Path, process::Output};
+use common::{Mock, CHANNEL, GUILD, USER};
+
+fn stderr(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stderr).into_owned()
+}
+
+fn write_config(path: &Path) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, format!(r#"
+tz = "+02:00"
+
+[[targets]]
+name = "nelly"
+user = "{USER}"
+channel = "{CHANNEL}"
+
+[[targets]]
+name = "nelly everywhere"
+user = "{USER}"
+guild = "{GUILD}"
+"#)).unwrap();
+}
+
+#[test]
+fn picks_a_saved_target_from_the_default_config() {
+    let mock = Mock::start(common::messages(10, common::may(20)));
+    let home = tempfile::tempdir().unwrap();
+    write_config(&home.path().join("config/how-active/config.toml"));
+
+    let out = mock.authed(home.path())
+        .args(["report", "--target", "nelly", "--range", "2023-05"])
+        .output()
+        .expect("run how-active");
+    assert!(out.status.success(), "{}", stderr(&out));
+    // the config's tz stands in for --tz
+    assert!(String::from_utf8_lossy(&out.stdout).contains("UTC+02:00"));
+
+    let searches = mock.searches();
+    assert_eq!(searches.len(), 1);
+    assert_eq!((searches[0]["author_id"].as_str(), searches[0]["channel_id"].as_str()), (USER, CHANNEL));
+}
+
+#[test]
+fn flags_override_parts_of_a_saved_target() {
+    let mock = Mock::start(common::messages(10, common::may(20)));
+    let home = tempfile::tempdir().unwrap();
+    let config = home.path().join("elsewhere.toml");
+    write_config(&config);
+
+    // a --channel takes the place of the saved guild, --tz of the saved zone
+    let out = mock.authed(home.path())
+        .args(["report", "--config", config.to_str().unwrap(), "--target", "nelly everywhere"])
+        .args(["--channel", CHANNEL, "--tz", "utc", "--range", "2023-05"])
+        .output()
+        .expect("run how-active");
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("UTC"));
+    assert!(!String::from_utf8_lossy(&out.stdout).contains("+02:00"));
+    assert_eq!(mock.searches()[0]["channel_id"], CHANNEL);
+}
+
+#[test]
+fn rejects_an_unknown_target() {
+    let mock = Mock::start(common::messages(10, common::may(20)));
+    let home = tempfile::tempdir().unwrap();
+    write_config(&home.path().join("config/how-active/config.toml"));
+
+    let out = mock.authed(home.path())
+        .args(["report", "--target", "nobody"])
+        .output()
+        .expect("run how-active");
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("No saved target called `nobody`"), "{}", stderr(&out));
+    assert!(mock.searches().is_empty());
+}
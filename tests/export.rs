@@ -0,0 +1,81 @@
+//! what comes out of a fetch, and reading it back in with --load
+
+mod common;
+
+use std::{fs, path::Path, process::Output};
+use common::{Mock, CHANNEL, USER};
+
+fn stderr(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stderr).into_owned()
+}
+
+fn run(mut cmd: std::process::Command, args: &[&str]) -> Output {
+    let out = cmd.args(args).output().expect("run how-active");
+    assert!(out.status.success(), "{}", stderr(&out));
+    out
+}
+
+// fetch the mock's messages into `home`/messages.ndjson
+fn dump(mock: &Mock, home: &Path) -> String {
+    let file = home.join("messages.ndjson").to_str().unwrap().to_string();
+    run(mock.authed(home), &["fetch", "--user", USER, "--channel", CHANNEL, "--range", "2023-05", "-o", &file]);
+    file
+}
+
+#[test]
+fn reports_on_a_dump_like_on_a_fetch() {
+    let mock = Mock::start(common::messages(60, common::may(20)));
+    let home = tempfile::tempdir().unwrap();
+    let file = dump(&mock, home.path());
+    assert_eq!(fs::read_to_string(&file).unwrap().lines().count(), 60);
+
+    let report = ["report", "--user", USER, "--channel", CHANNEL, "--range", "2023-05", "--tz", "utc", "--format", "csv"];
+    let fetched = run(mock.authed(home.path()), &report);
+    let before = mock.searches().len();
+    let loaded = run(mock.command(home.path()), &[&report[..], &["--load", &file]].concat());
+    // no token and no requests, it's all in the file
+    assert_eq!(mock.searches().len(), before);
+
+    let rows = |out: &Output| String::from_utf8_lossy(&out.stdout).lines()
+        .filter(|l| !l.starts_with('#'))
+        .map(String::from)
+        .collect::<Vec<String>>();
+    assert_eq!(rows(&loaded), rows(&fetched));
+    assert!(String::from_utf8_lossy(&loaded.stdout).contains("# fetched: 60 of 60"));
+}
+
+#[test]
+fn exports_the_numbers_as_csv_and_json() {
+    let mock = Mock::start(common::messages(60, common::may(20)));
+    let home = tempfile::tempdir().unwrap();
+    let csv = home.path().join("activity.csv");
+    let json = home.path().join("activity.json");
+    for path in [&csv, &json] {
+        run(mock.authed(home.path()), &["export", "--user", USER, "--channel", CHANNEL, "--range", "2023-05",
+            "--tz", "utc", "-o", path.to_str().unwrap()]);
+    }
+
+    let csv = fs::read_to_string(csv).unwrap();
+    let header = csv.lines().take_while(|l| l.starts_with('#')).collect::<Vec<&str>>();
+    assert!(header.contains(&format!("# user: nelly#1337 ({})", USER).as_str()), "{:?}", header);
+    assert!(header.contains(&format!("# source: general ({})", CHANNEL).as_str()), "{:?}", header);
+    assert!(header.iter().any(|l| l.starts_with("# fetched_at: ")), "{:?}", header);
+    assert!(header.contains(&"# zone: UTC"), "{:?}", header);
+    assert!(header.contains(&"# range: 2023-05-01 – 2023-05-31"), "{:?}", header);
+    assert!(header.contains(&"# fetched: 60 of 60"), "{:?}", header);
+    // a message every three hours from the 20th back covers the 12th to the 20th
+    let rows = |series: &str| csv.lines().filter(|l| l.starts_with(&format!("{},", series))).count();
+    assert_eq!((rows("hour"), rows("weekday_hour"), rows("daily")), (24, 7 * 24, 9));
+
+    let json = serde_json::from_str::<serde_json::Value>(&fs::read_to_string(json).unwrap()).unwrap();
+    let meta = &json["meta"];
+    assert_eq!(meta["user"]["id"], USER);
+    assert_eq!(meta["source"]["channel"]["id"], CHANNEL);
+    assert_eq!(meta["zone"], "UTC");
+    assert_eq!((meta["fetched"].as_u64(), meta["total_results"].as_u64()), (Some(60), Some(60)));
+    assert_eq!(json["utc_offset"], "+00:00");
+    assert_eq!(json["hours"].as_array().unwrap().len(), 24);
+    assert_eq!(json["hours"].as_array().unwrap().iter().filter_map(|n| n.as_u64()).sum::<u64>(), 60);
+    assert_eq!(json["weekday_hours"].as_array().unwrap().len(), 7);
+    assert_eq!(json["daily"].as_object().unwrap().len(), 9);
+}
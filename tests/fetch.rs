@@ -0,0 +1,211 @@
+//! fetching end to end, the binary against a mock discord
+
+mod common;
+
+use std::{collections::HashSet, fs, path::Path, process::Output};
+use common::{Mock, Reply, CHANNEL, USER};
+use chrono::{TimeZone, Utc};
+use how_active::discord::Snowflake;
+use serde_json::Value;
+
+fn fetch(mock: &Mock, home: &Path, extra: &[&str]) -> Output {
+    mock.authed(home)
+        .args(["fetch", "--user", USER, "--channel", CHANNEL, "--range", "2023-05"])
+        .args(extra)
+        .output()
+        .expect("run how-active")
+}
+
+fn ids(path: &Path) -> Vec<String> {
+    fs::read_to_string(path).expect("output")
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).expect("ndjson")["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+fn stderr(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stderr).into_owned()
+}
+
+#[test]
+fn pages_through_every_message() {
+    let mock = Mock::start(common::messages(60, common::may(20)));
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("out.ndjson");
+
+    let out = fetch(&mock, home.path(), &["-o", file.to_str().unwrap()]);
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let ids = ids(&file);
+    assert_eq!(ids.len(), 60);
+    assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 60);
+
+    let searches = mock.searches();
+    let offsets = searches.iter().map(|q| q.get("offset").map(String::as_str)).collect::<Vec<_>>();
+    assert_eq!(offsets, [None, Some("25"), Some("50")]);
+    assert!(searches.iter().all(|q| q["channel_id"] == CHANNEL && q["author_id"] == USER));
+}
+
+#[test]
+fn waits_out_rate_limits() {
+    let mock = Mock::start(common::messages(10, common::may(20)));
+    mock.script([Reply::RateLimited(0.2)]);
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("out.ndjson");
+
+    let out = fetch(&mock, home.path(), &["-o", file.to_str().unwrap()]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(stderr(&out).contains("Rate limited"));
+    assert_eq!(mock.searches().len(), 2);
+    assert_eq!(ids(&file).len(), 10);
+}
+
+#[test]
+fn waits_for_indexing() {
+    let mock = Mock::start(common::messages(10, common::may(20)));
+    mock.script([Reply::Indexing(0.1), Reply::Indexing(0.1)]);
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("out.ndjson");
+
+    let out = fetch(&mock, home.path(), &["-o", file.to_str().unwrap()]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert_eq!(mock.searches().len(), 3);
+    assert_eq!(ids(&file).len(), 10);
+}
+
+#[test]
+fn rejects_a_bad_token() {
+    let mock = Mock::start(common::messages(10, common::may(20)));
+    let home = tempfile::tempdir().unwrap();
+
+    let token = home.path().join("token");
+    fs::write(&token, "not-it\n").unwrap();
+
+    let out = mock.command(home.path())
+        .args(["fetch", "--token-file", token.to_str().unwrap(), "--user", USER, "--channel", CHANNEL])
+        .output()
+        .expect("run how-active");
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("Invalid auth token"), "{}", stderr(&out));
+    assert!(mock.searches().is_empty());
+}
+
+#[test]
+fn only_fetches_whats_newer_than_the_cache() {
+    let messages = common::messages(60, common::may(20));
+    let newest = messages[0]["id"].as_str().unwrap().to_string();
+    let mock = Mock::start(messages);
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("out.ndjson");
+
+    let out = fetch(&mock, home.path(), &["-o", file.to_str().unwrap()]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    let before = mock.searches().len();
+
+    let out = mock.authed(home.path())
+        .args(["report", "--user", USER, "--channel", CHANNEL, "--range", "2023-05", "--tz", "utc"])
+        .output()
+        .expect("run how-active");
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("60 of 60 messages"));
+
+    let searches = mock.searches();
+    assert_eq!(searches.len(), before + 1);
+    assert_eq!(searches[before]["min_id"], newest);
+}
+
+#[test]
+fn resumes_where_a_failed_fetch_stopped() {
+    let mock = Mock::start(common::messages(60, common::may(20)));
+    mock.script([Reply::Page, Reply::Status(403)]);
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("out.ndjson");
+
+    let out = fetch(&mock, home.path(), &["-o", file.to_str().unwrap()]);
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("Missing access"), "{}", stderr(&out));
+    let before = mock.searches().len();
+
+    let out = mock.authed(home.path())
+        .args(["fetch", "--resume", "-o", file.to_str().unwrap()])
+        .output()
+        .expect("run how-active");
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert!(stderr(&out).contains("Resuming with 25 messages already fetched"));
+    assert_eq!(ids(&file).len(), 60);
+    assert_eq!(mock.searches()[before].get("offset").map(String::as_str), Some("25"));
+}
+
+#[test]
+fn slides_the_window_past_the_offset_limit() {
+    let messages = common::messages(5110, common::may(20));
+    let mock = Mock::start(messages);
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("out.ndjson");
+
+    let out = mock.authed(home.path())
+        .args(["fetch", "--user", USER, "--channel", CHANNEL, "-o", file.to_str().unwrap()])
+        .output()
+        .expect("run how-active");
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let ids = ids(&file);
+    assert_eq!(ids.len(), 5110);
+    assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 5110);
+
+    // search won't go past offset 5000, so after the page at 4975 the
+    // window moves down to end at the oldest message so far
+    let searches = mock.searches();
+    let last = searches.iter().position(|q| q.get("offset").map(String::as_str) == Some("4975")).unwrap();
+    assert!(searches[..=last].iter().all(|q| !q.contains_key("max_id")));
+    let next = &searches[last + 1];
+    assert_eq!(next.get("offset"), None);
+    assert_eq!(next.get("max_id"), Some(&ids[4999]));
+}
+
+#[test]
+fn fills_in_between_the_cache_and_a_later_range() {
+    let mock = Mock::start(common::messages(400, common::may(20)));
+    let home = tempfile::tempdir().unwrap();
+    let report = |range: &str| {
+        let out = mock.authed(home.path())
+            .args(["report", "--user", USER, "--channel", CHANNEL, "--tz", "utc", "--range", range])
+            .output()
+            .expect("run how-active");
+        assert!(out.status.success(), "{}", stderr(&out));
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    };
+
+    // cached up to mid april, then may, which mustn't leave the rest of april out
+    report("..2023-04-15");
+    report("2023-05");
+    assert!(report("2023-04-20..2023-04-30").contains("88 of 88 messages"));
+}
+
+#[test]
+fn keeps_the_cache_through_a_run_that_starts_later() {
+    let mock = Mock::start(common::messages(400, common::may(20)));
+    let home = tempfile::tempdir().unwrap();
+    let report = |range: &str| {
+        let out = mock.authed(home.path())
+            .args(["report", "--user", USER, "--channel", CHANNEL, "--tz", "utc", "--range", range])
+            .output()
+            .expect("run how-active");
+        assert!(out.status.success(), "{}", stderr(&out));
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    };
+
+    // the last message the range takes in, 9pm on the 15th of april
+    report("..2023-04-15");
+    let newest = common::messages(400, common::may(20)).iter()
+        .map(|m| m["id"].as_str().unwrap().to_string())
+        .find(|id| Snowflake(id.parse().unwrap()).timestamp() < Utc.with_ymd_and_hms(2023, 4, 16, 0, 0, 0).unwrap())
+        .unwrap();
+    report("2023-05");
+
+    // april still comes from the first run's cache, only what's newer gets fetched
+    let before = mock.searches().len();
+    assert!(report("2023-04").contains("240 of 240 messages"));
+    let searches = mock.searches();
+    assert_eq!(searches[before]["min_id"], newest);
+}
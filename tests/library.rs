@@ -0,0 +1,67 @@
+//! using the library directly, without the binary in between
+
+mod common;
+
+use chrono::NaiveDate;
+use common::{Mock, CHANNEL, GUILD, TOKEN, USER};
+use how_active::{analysis::Tally, discord::{self, Client, Messenger, Token}, zone::Zone};
+
+fn client(mock: &Mock) -> Client {
+    Client::new(Token::new(TOKEN).unwrap()).with_api_base(&mock.url)
+}
+
+#[test]
+fn looks_up_the_target() {
+    let mock = Mock::start(common::messages(10, common::may(20)));
+    let client = client(&mock);
+
+    let user = discord::get_user(USER, &client).unwrap();
+    assert_eq!(user.username, "nelly");
+    let channel = discord::get_channel(CHANNEL, &client).unwrap();
+    assert_eq!(channel.guild_id, GUILD);
+}
+
+#[test]
+fn fetches_and_tallies() {
+    let mock = Mock::start(common::messages(40, common::may(20)));
+    let messenger = Messenger::new(client(&mock), USER.into(), GUILD.into(), Some(CHANNEL.into()));
+
+    let mut tally = Tally::new("utc".parse::<Zone>().unwrap());
+    for page in messenger {
+        page.unwrap().iter().for_each(|m| tally.add(m));
+    }
+
+    // three hours apart from midnight back, so only every third hour is hit
+    assert_eq!(tally.hours().iter().sum::<u32>(), 40);
+    assert!(tally.hours().iter().enumerate().all(|(h, &n)| (h % 3 == 0) == (n > 0)));
+
+    let stats = tally.stats(NaiveDate::from_ymd_opt(2023, 5, 20).unwrap());
+    assert_eq!(stats.messages, 40);
+    assert_eq!(stats.active_days, 6);
+    assert_eq!(stats.streaks.active.map(|r| r.days), Some(6));
+    assert_eq!(stats.streaks.gap, None);
+}
+
+#[test]
+fn clients_keep_to_their_own_server() {
+    let first = Mock::start(common::messages(10, common::may(20)));
+    let second = Mock::start(common::messages(30, common::may(20)));
+
+    let count = |mock: &Mock| Messenger::new(client(mock), USER.into(), GUILD.into(), Some(CHANNEL.into()))
+        .map(|page| page.unwrap().len())
+        .sum::<usize>();
+    assert_eq!(count(&first), 10);
+    assert_eq!(count(&second), 30);
+    assert_eq!(first.searches().len(), 1);
+    assert_eq!(second.searches().len(), 2);
+}
+
+#[test]
+fn no_guild_is_an_error_not_a_panic() {
+    let mock = Mock::start(common::messages(10, common::may(20)));
+    // a DM channel has no guild to search
+    let mut messenger = Messenger::new(client(&mock), USER.into(), String::new(), Some(CHANNEL.into()));
+    assert!(matches!(messenger.next(), Some(Err(discord::Error::NoTarget))));
+    assert!(messenger.next().is_none());
+    assert!(mock.searches().is_empty());
+}
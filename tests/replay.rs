@@ -0,0 +1,106 @@
+//! recording a fetch and playing it back offline
+
+mod common;
+
+use std::{fs, path::Path, process::Output};
+use common::{Mock, Reply, CHANNEL, TOKEN, USER};
+
+fn stderr(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stderr).into_owned()
+}
+
+fn report(mock: &Mock, home: &Path, args: &[&str]) -> Output {
+    mock.authed(home)
+        .args(["report", "--user", USER, "--channel", CHANNEL, "--range", "2023-05", "--tz", "utc", "--format", "json"])
+        .args(args)
+        .output()
+        .expect("run how-active")
+}
+
+#[test]
+fn replays_a_recording_offline() {
+    let mock = Mock::start(common::messages(60, common::may(20)));
+    mock.script([Reply::Page, Reply::RateLimited(0.2)]);
+    let home = tempfile::tempdir().unwrap();
+    let tape = home.path().join("session.ndjson");
+    let tape = tape.to_str().unwrap();
+
+    let recorded = report(&mock, home.path(), &["--record", tape]);
+    assert!(recorded.status.success(), "{}", stderr(&recorded));
+    // every search, the rate limited one included
+    let recording = fs::read_to_string(tape).unwrap();
+    assert_eq!(recording.lines().filter(|l| l.contains("/messages/search")).count(), mock.searches().len());
+    assert!(!recording.contains(TOKEN));
+
+    // nothing's listening anymore, and there's no token either
+    let mut replay = mock.command(home.path());
+    drop(mock);
+    let replayed = replay
+        .args(["report", "--user", USER, "--channel", CHANNEL, "--range", "2023-05", "--tz", "utc", "--format", "json"])
+        .args(["--replay", tape])
+        .output()
+        .expect("run how-active");
+    assert!(replayed.status.success(), "{}", stderr(&replayed));
+    assert_eq!(String::from_utf8_lossy(&replayed.stdout), String::from_utf8_lossy(&recorded.stdout));
+}
+
+#[test]
+fn replay_fails_on_a_request_it_never_saw() {
+    let mock = Mock::start(common::messages(10, common::may(20)));
+    let home = tempfile::tempdir().unwrap();
+    let tape = home.path().join("session.ndjson");
+    let tape = tape.to_str().unwrap();
+
+    let out = report(&mock, home.path(), &["--record", tape]);
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let out = mock.command(home.path())
+        .args(["report", "--user", "1234", "--channel", CHANNEL, "--replay", tape])
+        .output()
+        .expect("run how-active");
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("Nothing recorded for /users/1234"), "{}", stderr(&out));
+}
+
+#[test]
+fn replay_fails_on_a_range_it_never_saw() {
+    let mock = Mock::start(common::messages(10, common::may(20)));
+    let home = tempfile::tempdir().unwrap();
+    let tape = home.path().join("session.ndjson");
+    let tape = tape.to_str().unwrap();
+
+    let out = report(&mock, home.path(), &["--record", tape]);
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let out = mock.command(home.path())
+        .args(["report", "--user", USER, "--channel", CHANNEL, "--range", "2023-04", "--replay", tape])
+        .output()
+        .expect("run how-active");
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("Nothing recorded for /guilds/"), "{}", stderr(&out));
+}
+
+#[test]
+fn replays_a_relative_range_as_it_was_recorded() {
+    let mock = Mock::start(common::messages(10, common::may(20)));
+    let home = tempfile::tempdir().unwrap();
+    let tape = home.path().join("session.ndjson");
+    let tape = tape.to_str().unwrap();
+
+    // counted back from now, which has moved on by the time it's replayed
+    let args = ["report", "--user", USER, "--channel", CHANNEL, "--range", "2000d", "--tz", "utc", "--format", "json"];
+    let recorded = mock.authed(home.path())
+        .args(args)
+        .args(["--record", tape])
+        .output()
+        .expect("run how-active");
+    assert!(recorded.status.success(), "{}", stderr(&recorded));
+
+    let replayed = mock.command(home.path())
+        .args(args)
+        .args(["--replay", tape])
+        .output()
+        .expect("run how-active");
+    assert!(replayed.status.success(), "{}", stderr(&replayed));
+    assert_eq!(String::from_utf8_lossy(&replayed.stdout), String::from_utf8_lossy(&recorded.stdout));
+}
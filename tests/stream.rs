@@ -0,0 +1,76 @@
+//! the async messenger against the mock, only built with `--features async`
+#![cfg(feature = "async")]
+
+mod common;
+
+use std::time::{Duration, Instant};
+use futures_util::StreamExt;
+use common::{Mock, Reply, CHANNEL, GUILD, TOKEN, USER};
+use how_active::{
+    discord::{Client, Messenger, Token, Wait},
+    stream::{AsyncMessenger, Limiter},
+    tape::Tape,
+};
+
+fn messenger(mock: &Mock) -> Messenger {
+    let client = Client::new(Token::new(TOKEN).unwrap()).with_api_base(&mock.url);
+    Messenger::new(client, USER.into(), GUILD.into(), Some(CHANNEL.into()))
+}
+
+#[tokio::test]
+async fn streams_every_page() {
+    let mock = Mock::start(common::messages(60, common::may(20)));
+
+    // two at once both get everything
+    let limiter = Limiter::new();
+    let tasks = [0, 1].map(|_| {
+        let mut pages = AsyncMessenger::new(messenger(&mock)).with_limiter(limiter.clone());
+        tokio::spawn(async move {
+            let mut fetched = 0;
+            while let Some(page) = pages.next().await {
+                fetched += page.unwrap().len();
+            }
+            (fetched, pages.fetched(), pages.total_results())
+        })
+    });
+    for task in tasks {
+        assert_eq!(task.await.unwrap(), (60, 60, 60));
+    }
+}
+
+#[tokio::test]
+async fn shares_rate_limits() {
+    let mock = Mock::start(common::messages(60, common::may(20)));
+    mock.script([Reply::RateLimited(0.5)]);
+
+    // one getting rate limited holds the other off too
+    let limiter = Limiter::new();
+    let mut first = AsyncMessenger::new(messenger(&mock)).with_limiter(limiter.clone());
+    let mut second = AsyncMessenger::new(messenger(&mock)).with_limiter(limiter.clone());
+    let start = Instant::now();
+    assert!(first.next().await.unwrap().unwrap().is_empty());
+    assert!(matches!(first.wait(), Some(Wait::RateLimit(_))));
+    assert!(limiter.remaining().is_some_and(|d| !d.is_zero()));
+
+    assert_eq!(second.next().await.unwrap().unwrap().len(), 25);
+    assert!(start.elapsed() >= Duration::from_millis(450));
+}
+
+#[tokio::test]
+async fn records_to_a_tape() {
+    let mock = Mock::start(common::messages(60, common::may(20)));
+    let home = tempfile::tempdir().unwrap();
+    let path = home.path().join("session.ndjson");
+
+    let client = Client::new(Token::new(TOKEN).unwrap())
+        .with_api_base(&mock.url)
+        .with_tape(Tape::record(&path).unwrap());
+    let messenger = Messenger::new(client, USER.into(), GUILD.into(), Some(CHANNEL.into()));
+    let mut pages = AsyncMessenger::new(messenger);
+    while let Some(page) = pages.next().await {
+        page.unwrap();
+    }
+
+    let recording = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(recording.lines().filter(|l| l.contains("/messages/search")).count(), 3);
+}